@@ -1,22 +1,116 @@
 extern crate marafet_parser as parser;
 extern crate marafet_util as util;
 
-use std::io::{Write, Result};
-use std::collections::HashMap;
+use std::io::{Write, Result, Error, ErrorKind};
+use std::collections::{HashMap, HashSet, BTreeSet};
 
 use parser::{Ast, Block};
-use parser::css::{Rule, Selector};
-use util::join;
+use parser::css::{Rule, Selector, Compound, Combinator, Item, AtRule};
+use parser::css::{Simple};
+use util::{join, Scoping, selector_classes};
 
 
 pub struct Settings<'a> {
     pub block_name: &'a String,
     pub vars: &'a HashMap<String, String>,
+    pub scoping: Scoping,
+}
+
+// Replaces every `&` of the nested selector with the outer selector. Nested
+// selector without `&` matches descendants of the outer one
+fn nest(outer: &Selector, nested: &Selector) -> Selector {
+    let outer_items = Some((Combinator::Descendant, &outer.first)).into_iter()
+        .chain(outer.rest.iter().map(|&(c, ref x)| (c, x)))
+        .map(|(c, x)| (c, x.clone()))
+        .collect::<Vec<_>>();
+    let mut items = vec![];
+    if !nested.has_parent() {
+        items.extend(outer_items.iter().cloned());
+    }
+    let nested_items = Some((Combinator::Descendant, &nested.first))
+        .into_iter()
+        .chain(nested.rest.iter().map(|&(c, ref x)| (c, x)));
+    for (comb, comp) in nested_items {
+        if comp.parent {
+            let mut replacement = outer_items.clone();
+            replacement[0].0 = comb;
+            replacement.last_mut().unwrap().1.simple
+                .extend(comp.simple.iter().cloned());
+            items.extend(replacement.into_iter());
+        } else {
+            items.push((comb, comp.clone()));
+        }
+    }
+    let (_, first) = items.remove(0);
+    Selector {
+        first: first,
+        rest: items,
+    }
+}
+
+fn collect_keyframes(items: &Vec<Item>, names: &mut HashSet<String>) {
+    for item in items.iter() {
+        match item {
+            &Item::Rule(ref rule) => collect_keyframes(&rule.nested, names),
+            &Item::AtRule(AtRule::Conditional(_, _, _, ref nested)) => {
+                collect_keyframes(nested, names);
+            }
+            &Item::AtRule(AtRule::Keyframes(ref name, _)) => {
+                names.insert(name.clone());
+            }
+            &Item::AtRule(AtRule::FontFace(_)) => {}
+        }
+    }
+}
+
+fn indent(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    let mut res = String::new();
+    for line in text.trim_right().lines() {
+        if line.len() > 0 {
+            res.push_str("    ");
+            res.push_str(line);
+        }
+        res.push('\n');
+    }
+    res
+}
+
+fn collect_classes(items: &Vec<Item>, names: &mut BTreeSet<String>) {
+    for item in items.iter() {
+        match item {
+            &Item::Rule(ref rule) => {
+                for comp in rule.selectors.iter().flat_map(|s| s.compounds()) {
+                    for simple in comp.simple.iter() {
+                        match simple {
+                            &Simple::Class(ref name) => {
+                                names.insert(name.clone());
+                            }
+                            // Classes in `:not(.x)` are renamed too
+                            &Simple::PseudoClass(_, Some(ref arg))
+                            if !simple.is_global() => {
+                                names.extend(selector_classes(arg));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                collect_classes(&rule.nested, names);
+            }
+            &Item::AtRule(AtRule::Conditional(_, _, _, ref nested)) => {
+                collect_classes(nested, names);
+            }
+            &Item::AtRule(_) => {}
+        }
+    }
 }
 
 struct Generator<'a, W: 'a> {
     block_name: &'a String,
+    scoping: Scoping,
+    global: bool,
     vars: &'a HashMap<&'a String, &'a String>,
+    keyframes: &'a HashSet<String>,
     buf: &'a mut W,
 }
 
@@ -24,39 +118,253 @@ struct Generator<'a, W: 'a> {
 
 impl<'a, W:Write+'a> Generator<'a, W> {
 
-    fn selector_to_string(&self, sel: &Selector) -> String {
+    // Every compound selector is scoped with the block class, so `.a > .b`
+    // only matches elements rendered by the block. Compound consisting of
+    // `:global(...)` only is written as is
+    fn compound_to_string(&self, comp: &Compound) -> String {
         let mut buf = Vec::new();
-        if let Some(ref element) = sel.element {
+        if let Some(ref element) = comp.element {
             write!(&mut buf, "{}", element).unwrap();
         }
-        write!(&mut buf, ".{}", self.block_name).unwrap();
-        for cls in sel.classes.iter() {
-            write!(&mut buf, ".{}", cls).unwrap();
+        let only_global = comp.element.is_none() &&
+            comp.simple.iter().all(|x| x.is_global());
+        if !only_global && !self.global {
+            write!(&mut buf, ".{}", self.scoping.marker(self.block_name))
+                .unwrap();
         }
-        if let Some(ref state) = sel.state {
-            write!(&mut buf, ":{}", state).unwrap();
+        for simple in comp.simple.iter() {
+            match simple {
+                &Simple::PseudoClass(ref name, Some(ref arg))
+                if name == "global" => {
+                    write!(&mut buf, "{}", arg).unwrap();
+                }
+                _ if self.global => write!(&mut buf, "{}", simple).unwrap(),
+                &Simple::Class(ref name) => {
+                    write!(&mut buf, ".{}", self.scoping.class_name(
+                        self.block_name, name)).unwrap();
+                }
+                &Simple::PseudoClass(ref name, Some(ref arg)) => {
+                    write!(&mut buf, ":{}({})", name, self.scoping
+                        .rename_classes(self.block_name, arg)).unwrap();
+                }
+                _ => write!(&mut buf, "{}", simple).unwrap(),
+            }
         }
         return String::from_utf8(buf).unwrap();
     }
 
-    fn output_rule(&mut self, rule: &Rule) -> Result<()>
+    fn selector_to_string(&self, sel: &Selector) -> String {
+        let mut res = self.compound_to_string(&sel.first);
+        for &(comb, ref comp) in sel.rest.iter() {
+            res.push_str(&format!("{}{}", comb,
+                                  self.compound_to_string(comp)));
+        }
+        return res;
+    }
+
+    // Replaces every `$name` in the value with the value of the variable,
+    // quoted strings are left as is
+    fn substitute(&self, value: &str) -> Result<String> {
+        let mut res = String::new();
+        let mut quote = None;
+        let mut chars = value.char_indices();
+        while let Some((idx, ch)) = chars.next() {
+            match (quote, ch) {
+                (Some(_), '\\') => {
+                    res.push(ch);
+                    if let Some((_, escaped)) = chars.next() {
+                        res.push(escaped);
+                    }
+                }
+                (Some(q), _) => {
+                    if ch == q {
+                        quote = None;
+                    }
+                    res.push(ch);
+                }
+                (None, '"') | (None, '\'') => {
+                    quote = Some(ch);
+                    res.push(ch);
+                }
+                (None, '$') => {
+                    let rest = &value[idx+1..];
+                    let end = rest.find(|c| match c {
+                        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => false,
+                        _ => true,
+                    }).unwrap_or(rest.len());
+                    let name = String::from(&rest[..end]);
+                    match self.vars.get(&name) {
+                        Some(value) => res.push_str(value),
+                        None => {
+                            return Err(Error::new(ErrorKind::InvalidInput,
+                                format!("Undefined CSS variable {:?}", name)));
+                        }
+                    }
+                    // Name is ascii-only, so it's as many chars as bytes
+                    for _ in 0..end {
+                        chars.next();
+                    }
+                }
+                (None, _) => res.push(ch),
+            }
+        }
+        Ok(res)
+    }
+
+    // Keyframes are global in CSS, so their names are scoped too
+    fn keyframes_name(&self, name: &str) -> String {
+        if self.global {
+            return String::from(name);
+        }
+        self.scoping.global_name(self.block_name, name)
+    }
+
+    fn rename_keyframes(&self, value: &str) -> String {
+        let mut res = String::new();
+        let mut start = None;
+        let end = Some((value.len(), ' '));
+        for (idx, ch) in value.char_indices().chain(end.into_iter()) {
+            let word_char = match ch {
+                'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => true,
+                _ => false,
+            };
+            match (start, word_char) {
+                (None, true) => start = Some(idx),
+                (Some(begin), false) => {
+                    let word = &value[begin..idx];
+                    if self.keyframes.contains(word) {
+                        res.push_str(&self.keyframes_name(word));
+                    } else {
+                        res.push_str(word);
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+            if !word_char && idx < value.len() {
+                res.push(ch);
+            }
+        }
+        res
+    }
+
+    fn output_block(&mut self, header: &str,
+        properties: &Vec<(String, String)>)
+        -> Result<()>
     {
-        let selectors = join(rule.selectors.iter()
-                             .map(|x| self.selector_to_string(x)), ", ");
-        try!(write!(self.buf, "{} {{\n", selectors));
-        for &(ref k, ref v) in rule.properties.iter() {
+        try!(write!(self.buf, "{} {{\n", header));
+        for &(ref k, ref v) in properties.iter() {
+            let mut v = try!(self.substitute(v));
+            if k == "animation" || k == "animation-name" {
+                v = self.rename_keyframes(&v);
+            }
             try!(write!(self.buf, "    {}: {};\n", k, v));
         }
         try!(write!(self.buf, "}}\n\n"));
         Ok(())
     }
+
+    // Writes whatever `f` generates, indented and wrapped in braces
+    fn output_wrapped<F>(&mut self, header: &str, f: F) -> Result<()>
+        where F: FnOnce(&mut Generator<Vec<u8>>) -> Result<()>
+    {
+        let mut inner = Vec::new();
+        {
+            let mut gen = Generator {
+                block_name: self.block_name,
+                scoping: self.scoping,
+                global: self.global,
+                vars: self.vars,
+                keyframes: self.keyframes,
+                buf: &mut inner,
+            };
+            try!(f(&mut gen));
+        }
+        try!(write!(self.buf, "{} {{\n{}}}\n\n", header, indent(&inner)));
+        Ok(())
+    }
+
+    fn output_rule(&mut self, rule: &Rule, outer: &[Selector]) -> Result<()>
+    {
+        let selectors = if outer.len() == 0 {
+            if rule.selectors.iter().any(|s| s.has_parent()) {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                    "`&` and leading combinators are only allowed \
+                     in nested rules"));
+            }
+            rule.selectors.clone()
+        } else {
+            outer.iter().flat_map(|o| rule.selectors.iter()
+                .map(move |s| nest(o, s))).collect()
+        };
+        // Rule having only nested rules produces no output by itself
+        if rule.properties.len() > 0 || rule.nested.len() == 0 {
+            let text = join(selectors.iter()
+                            .map(|x| self.selector_to_string(x)), ", ");
+            try!(self.output_block(&text, &rule.properties));
+        }
+        self.output_items(&rule.nested, &selectors)
+    }
+
+    fn output_at_rule(&mut self, rule: &AtRule, outer: &[Selector])
+        -> Result<()>
+    {
+        match rule {
+            &AtRule::Conditional(ref name, ref condition, ref properties,
+                                 ref items) => {
+                if properties.len() > 0 && outer.len() == 0 {
+                    return Err(Error::new(ErrorKind::InvalidInput,
+                        format!("Properties in {} are only allowed when it's \
+                                 nested in a rule", name)));
+                }
+                let header = format!("{} {}", name,
+                                     try!(self.substitute(condition)));
+                self.output_wrapped(&header, |gen| {
+                    if properties.len() > 0 {
+                        let text = join(outer.iter()
+                            .map(|x| gen.selector_to_string(x)), ", ");
+                        try!(gen.output_block(&text, properties));
+                    }
+                    gen.output_items(items, outer)
+                })
+            }
+            &AtRule::Keyframes(ref name, ref frames) => {
+                let header = format!("@keyframes {}",
+                                     self.keyframes_name(name));
+                self.output_wrapped(&header, |gen| {
+                    for &(ref selectors, ref properties) in frames.iter() {
+                        try!(gen.output_block(&selectors.join(", "),
+                                              properties));
+                    }
+                    Ok(())
+                })
+            }
+            &AtRule::FontFace(ref properties) => {
+                self.output_block("@font-face", properties)
+            }
+        }
+    }
+
+    fn output_items(&mut self, items: &Vec<Item>, outer: &[Selector])
+        -> Result<()>
+    {
+        for item in items.iter() {
+            match item {
+                &Item::Rule(ref rule) => try!(self.output_rule(rule, outer)),
+                &Item::AtRule(ref rule) => {
+                    try!(self.output_at_rule(rule, outer));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn generate<W>(buf: &mut W, ast: &Ast, settings: &Settings) -> Result<()>
     where W: Write
 {
     for block in ast.blocks.iter() {
-        if let &Block::Css(ref params, ref rules) = block {
+        if let &Block::Css(ref params, ref items, global) = block {
             let mut vars = HashMap::new();
             for param in params.iter() {
                 if let Some(ref val) = param.default_value {
@@ -66,15 +374,35 @@ pub fn generate<W>(buf: &mut W, ast: &Ast, settings: &Settings) -> Result<()>
             for (key, val) in settings.vars.iter() {
                 vars.insert(key, val);
             }
-            for rule in rules.iter() {
-                let mut gen = Generator {
-                    block_name: settings.block_name,
-                    vars: &vars,
-                    buf: buf,
-                };
-                try!(gen.output_rule(rule));
+            let mut keyframes = HashSet::new();
+            if !global {
+                collect_keyframes(items, &mut keyframes);
             }
+            let mut gen = Generator {
+                block_name: settings.block_name,
+                scoping: settings.scoping,
+                global: global,
+                vars: &vars,
+                keyframes: &keyframes,
+                buf: buf,
+            };
+            try!(gen.output_items(items, &[]));
         }
     }
     Ok(())
 }
+
+// Returns pairs of class name used in css blocks and the name in the
+// generated stylesheet
+pub fn class_map(ast: &Ast, settings: &Settings) -> Vec<(String, String)> {
+    let mut names = BTreeSet::new();
+    for block in ast.blocks.iter() {
+        if let &Block::Css(_, ref items, false) = block {
+            collect_classes(items, &mut names);
+        }
+    }
+    names.into_iter().map(|name| {
+        let scoped = settings.scoping.class_name(settings.block_name, &name);
+        (name, scoped)
+    }).collect()
+}
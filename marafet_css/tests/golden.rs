@@ -0,0 +1,145 @@
+// Compiles `golden/<name>.mft` and compares the result with
+// `golden/<name>.css`, set MARAFET_BLESS=1 to rewrite the expected output
+extern crate marafet_parser as parser;
+extern crate marafet_util as util;
+extern crate marafet_css as css;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use util::Scoping;
+
+
+fn read(path: &str) -> String {
+    let mut buf = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut buf))
+        .expect(path);
+    buf
+}
+
+fn compile(source: &str, settings: &css::Settings) -> Result<String, String> {
+    let ast = parser::parse_string(source).unwrap();
+    let mut buf = Vec::new();
+    try!(css::generate(&mut buf, &ast, settings).map_err(|e| e.to_string()));
+    Ok(String::from_utf8(buf).unwrap())
+}
+
+fn settings<'a>(block_name: &'a String, vars: &'a HashMap<String, String>)
+    -> css::Settings<'a>
+{
+    css::Settings {
+        block_name: block_name,
+        vars: vars,
+        scoping: Scoping::BlockClass,
+    }
+}
+
+fn check(name: &str, settings: &css::Settings) {
+    let source = read(&format!("tests/golden/{}.mft", name));
+    let result = compile(&source, settings).unwrap();
+    let path = format!("tests/golden/{}.css", name);
+    if env::var("MARAFET_BLESS").is_ok() {
+        File::create(&path).and_then(|mut f| f.write_all(result.as_bytes()))
+            .expect(&path);
+    } else if result != read(&path) {
+        panic!("Output of {}.mft differs, got:\n{}", name, result);
+    }
+}
+
+fn error(source: &str) -> String {
+    let name = String::from("error");
+    let vars = HashMap::new();
+    match compile(source, &settings(&name, &vars)) {
+        Ok(_) => panic!("Compiled successfully: {:?}", source),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn vars() {
+    let name = String::from("vars");
+    let mut vars = HashMap::new();
+    vars.insert(String::from("size"), String::from("14px"));
+    check("vars", &settings(&name, &vars));
+}
+
+#[test]
+fn undefined_var() {
+    let err = error("css:\n  .a\n    color: $color\n");
+    assert!(err.contains("Undefined CSS variable \"color\""), "{}", err);
+}
+
+#[test]
+fn selectors() {
+    let name = String::from("selectors");
+    check("selectors", &settings(&name, &HashMap::new()));
+}
+
+#[test]
+fn nesting() {
+    let name = String::from("nesting");
+    check("nesting", &settings(&name, &HashMap::new()));
+}
+
+#[test]
+fn at_rules() {
+    let name = String::from("at_rules");
+    check("at_rules", &settings(&name, &HashMap::new()));
+}
+
+#[test]
+fn values() {
+    let name = String::from("values");
+    check("values", &settings(&name, &HashMap::new()));
+}
+
+#[test]
+fn scoping() {
+    let vars = HashMap::new();
+    let name = String::from("scoping");
+    check("scoping", &settings(&name, &vars));
+    let name = String::from("scoping_prefix");
+    let mut s = settings(&name, &vars);
+    s.scoping = Scoping::Prefix;
+    check("scoping_prefix", &s);
+    let name = String::from("scoping_hash");
+    let mut s = settings(&name, &vars);
+    s.scoping = Scoping::Hash;
+    check("scoping_hash", &s);
+}
+
+#[test]
+fn global() {
+    let name = String::from("global");
+    let vars = HashMap::new();
+    let mut s = settings(&name, &vars);
+    s.scoping = Scoping::Prefix;
+    check("global", &s);
+}
+
+#[test]
+fn quoted_vars() {
+    let name = String::from("quoted_vars");
+    let mut vars = HashMap::new();
+    vars.insert(String::from("size"), String::from("14px"));
+    check("quoted_vars", &settings(&name, &vars));
+}
+
+#[test]
+fn class_map() {
+    let ast = parser::parse_string(&read("tests/golden/scoping.mft"))
+        .unwrap();
+    let name = String::from("blk");
+    let vars = HashMap::new();
+    let mut s = settings(&name, &vars);
+    s.scoping = Scoping::Prefix;
+    // Classes used in `:not()` are renamed in the stylesheet, so they must
+    // be renamed in templates too
+    assert_eq!(css::class_map(&ast, &s), vec![
+        (String::from("button"), String::from("blk-button")),
+        (String::from("disabled"), String::from("blk-disabled")),
+        (String::from("primary"), String::from("blk-primary")),
+    ]);
+}
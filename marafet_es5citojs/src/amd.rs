@@ -40,7 +40,7 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 &Block::ImportModule(ref name, ref source) => {
                     dependencies.push(Str(source.clone()));
                     arguments.push(Param {
-                        name: name.clone(),
+                        name: self.ident(name),
                         default_value: None,
                         });
                 }
@@ -57,7 +57,7 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                     let varname = &modules[source];
                     for &(ref name, ref alias) in items.iter() {
                         code_prefix.push(
-                            Var(alias.as_ref().unwrap_or(name).clone(),
+                            Var(self.ident(alias.as_ref().unwrap_or(name)),
                                 Attr(Box::new(Name(varname.clone())),
                                      name.clone())));
                     }
@@ -66,13 +66,18 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                     code_suffix.push(Expr(AssignAttr(
                         Box::new(Name(String::from("exports"))),
                         name.clone(),
-                        Box::new(Name(String::from(name.clone()))),
+                        Box::new(Name(self.ident(name))),
                         )));
                 }
-                // TODO(tailhook) may be export css too?
-                &Block::Css(_, _) => {}
+                &Block::Css(_, _, _) => {}
+                &Block::Const(_, _) => {}
             }
         }
+        for (name, value) in self.css_exports().into_iter() {
+            code_suffix.push(Expr(AssignAttr(
+                Box::new(Name(String::from("exports"))),
+                name, Box::new(value))));
+        }
         let mut body = code_prefix;
         body.extend(code.statements.into_iter());
         body.extend(code_suffix.into_iter());
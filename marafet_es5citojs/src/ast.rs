@@ -11,6 +11,10 @@ pub enum Statement {
     Return(Expression),
     Function(String, Vec<Param>, Vec<Statement>),
     Var(String, Expression),
+    If(Expression, Vec<Statement>),
+    For(String, Expression, Expression, Expression, Vec<Statement>),
+    Throw(Expression),
+    Try(Vec<Statement>, String, Vec<Statement>),
 }
 
 
@@ -32,6 +36,7 @@ pub enum Expression {
     Call(Box<Expression>, Vec<Expression>),
     Function(Option<String>, Vec<Param>, Vec<Statement>),
     AssignAttr(Box<Expression>, String, Box<Expression>),
+    Assign(Box<Expression>, Box<Expression>),
     Ternary(Box<Expression>, Box<Expression>, Box<Expression>),
     New(Box<Expression>),
     Not(Box<Expression>),
@@ -42,5 +47,7 @@ pub enum Expression {
     Div(Box<Expression>, Box<Expression>),
     Mul(Box<Expression>, Box<Expression>),
     Comparison(Comparator, Box<Expression>, Box<Expression>),
+    TypeOf(Box<Expression>),
+    InstanceOf(Box<Expression>, Box<Expression>),
 }
 
@@ -1,19 +1,47 @@
 use std::collections::HashSet;
 
 use parser::{Ast, Block};
+use parser::css::{Rule, Simple, Item, AtRule};
 
 
+// Compounds without a class and an element name, like `#main` or
+// `[disabled]`, may match any element, so `*` is returned for them
+fn rule_elements(rule: &Rule, res: &mut HashSet<String>) {
+    for comp in rule.selectors.iter().flat_map(|s| s.compounds()) {
+        let has_class = comp.simple.iter().any(|x| match x {
+            &Simple::Class(_) => true,
+            _ => false,
+        });
+        let only_global = comp.simple.len() > 0 &&
+            comp.simple.iter().all(|x| x.is_global());
+        // `&` is replaced by the compound of the outer rule
+        if has_class || comp.parent || (only_global && comp.element.is_none())
+        {
+            continue;
+        }
+        res.insert(comp.element.clone().unwrap_or(String::from("*")));
+    }
+    items_elements(&rule.nested, res);
+}
+
+fn items_elements(items: &Vec<Item>, res: &mut HashSet<String>) {
+    for item in items.iter() {
+        match item {
+            &Item::Rule(ref rule) => rule_elements(rule, res),
+            &Item::AtRule(AtRule::Conditional(_, _, _, ref nested)) => {
+                items_elements(nested, res);
+            }
+            &Item::AtRule(_) => {}
+        }
+    }
+}
+
 pub fn visitor(ast: &Ast) -> HashSet<String> {
     let mut res = HashSet::new();
     for block in ast.blocks.iter() {
-        if let &Block::Css(_, ref rules) = block {
-            for rule in rules.iter() {
-                for sel in rule.selectors.iter() {
-                    if sel.classes.len() == 0 && sel.element.is_some() {
-                        res.insert(sel.element.as_ref().unwrap().clone());
-                    }
-                }
-            }
+        // Elements styled by global blocks don't need the block class
+        if let &Block::Css(_, ref items, false) = block {
+            items_elements(items, &mut res);
         }
     }
     return res;
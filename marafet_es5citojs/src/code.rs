@@ -3,39 +3,103 @@ use std::io::{Write};
 use parser::html;
 use parser::html::Expression as Expr;
 use parser::html::Statement as Stmt;
-use parser::html::{Fmt};
+use parser::html::{Fmt, Type, Comparator};
 use parser::html::Statement::{Element, Store, Condition, Output};
 use parser::{Ast, Block};
 
 use super::ast::{Code, Statement, Param, Expression};
 
 use super::Generator;
+use super::names;
 
 
+// Every branch gets a key even without a parent key, so that switching
+// branches replaces nodes instead of patching them
 fn key_num(sup: &Option<Expression>, sub: usize) -> Option<Expression> {
-    sup.as_ref().map(|k| {
-        Expression::Add(
+    match sup {
+        &Some(Expression::Str(ref k)) => Some(Expression::Str(
+            format!("{}:{}", k, sub))),
+        &Some(ref k) => Some(Expression::Add(
             Box::new(k.clone()),
-            Box::new(Expression::Str(format!(":{}", sub))))
-    })
+            Box::new(Expression::Str(format!(":{}", sub))))),
+        &None => Some(Expression::Str(format!(":{}", sub))),
+    }
 }
 
-fn key_join(sup: Option<Expression>, sub: &String) -> Expression {
+// Text can't have a key, so it's wrapped into a fragment
+fn keyed(value: Expression, key: Option<Expression>) -> Expression {
+    match key {
+        Some(key) => Expression::Object(vec![
+            (String::from("key"), key),
+            (String::from("children"), value),
+            ]),
+        None => value,
+    }
+}
+
+// Name of the variable must already be converted by `Generator::ident`
+pub fn key_join(sup: Option<Expression>, sub: &String) -> Expression {
     sup.map(|k| {
-        Expression::Add(
-            Box::new(Expression::Add(
+        let prefix = match k {
+            Expression::Str(k) => Expression::Str(format!("{}:", k)),
+            k => Expression::Add(
                 Box::new(k),
-                Box::new(Expression::Str(String::from(":"))))),
+                Box::new(Expression::Str(String::from(":")))),
+        };
+        Expression::Add(
+            Box::new(prefix),
             Box::new(Expression::Name(sub.clone())))
     }).unwrap_or(Expression::Name(sub.clone()))
 }
 
 impl<'a, W:Write+'a> Generator<'a, W> {
 
+    fn type_check(&self, block: &String, name: &String, var_name: String,
+        typ: &Type)
+        -> Statement
+    {
+        let var = || Box::new(Expression::Name(var_name.clone()));
+        let type_of = |js_type: &str| Expression::Comparison(Comparator::Eq,
+            Box::new(Expression::TypeOf(var())),
+            Box::new(Expression::Str(String::from(js_type))));
+        let cond = match typ {
+            &Type::Str => type_of("string"),
+            &Type::Float => type_of("number"),
+            &Type::Bool => type_of("boolean"),
+            &Type::Int => Expression::And(
+                Box::new(type_of("number")),
+                Box::new(Expression::Comparison(Comparator::Eq,
+                    Box::new(Expression::Call(
+                        Box::new(Expression::Attr(
+                            Box::new(Expression::Name(String::from("Math"))),
+                            String::from("floor"))),
+                        vec![*var()])),
+                    var()))),
+            &Type::List => Expression::Call(
+                Box::new(Expression::Attr(
+                    Box::new(Expression::Name(String::from("Array"))),
+                    String::from("isArray"))),
+                vec![*var()]),
+            &Type::Dict => Expression::And(
+                Box::new(type_of("object")),
+                Box::new(Expression::Comparison(Comparator::NotEq,
+                    var(), Box::new(Expression::Name(String::from("null")))))),
+            &Type::Class(ref cls) => Expression::InstanceOf(var(),
+                Box::new(Expression::Name(self.ident(cls)))),
+        };
+        Statement::If(Expression::Not(Box::new(cond)), vec![
+            Statement::Throw(Expression::New(Box::new(Expression::Call(
+                Box::new(Expression::Name(String::from("TypeError"))),
+                vec![Expression::Str(format!(
+                    "Parameter {:?} of {:?} must be {}",
+                    name, block, typ))])))),
+        ])
+    }
+
     pub fn compile_expr(&self, expr: &Expr) -> Expression
     {
         match expr {
-            &Expr::Name(ref name) => Expression::Name(name.clone()),
+            &Expr::Name(ref name) => Expression::Name(self.ident(name)),
             &Expr::Str(ref value) => Expression::Str(value.clone()),
 
             // TODO(tailhook) validate numeric suffixes
@@ -57,9 +121,8 @@ impl<'a, W:Write+'a> Generator<'a, W> {
             &Expr::Item(ref expr, ref item)
             => Expression::Item(Box::new(self.compile_expr(expr)),
                                 Box::new(self.compile_expr(item))),
-            &Expr::Call(ref expr, ref args)
-            => Expression::Call(Box::new(self.compile_expr(expr)),
-                args.iter().map(|x| self.compile_expr(x)).collect()),
+            &Expr::Call(ref expr, ref args, ref kwargs)
+            => self.compile_call(expr, args, kwargs, None),
             &Expr::Add(ref a, ref b)
             => Expression::Add(Box::new(self.compile_expr(a)),
                                Box::new(self.compile_expr(b))),
@@ -90,6 +153,87 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 .collect()),
         }
     }
+    // Keyword arguments are resolved at compile time if callee is known,
+    // otherwise callee's `$kw` entry point is used, if it exists, or keywords
+    // are passed as a trailing options object to a plain javascript function
+    pub fn compile_call(&self, func: &Expr, args: &Vec<Expr>,
+        kwargs: &Vec<(String, Expr)>, events: Option<Expression>)
+        -> Expression
+    {
+        let template = match func {
+            &Expr::Name(ref name) => self.templates.get(name),
+            _ => None,
+        };
+        let mut args = args.iter()
+            .map(|x| self.compile_expr(x))
+            .collect::<Vec<_>>();
+        if let Some(template) = template {
+            let mut positional = args.into_iter().map(Some)
+                .collect::<Vec<_>>();
+            positional.resize(template.params.len(), None);
+            for &(ref name, ref value) in kwargs.iter() {
+                let idx = template.params.iter().position(|p| p == name)
+                    .unwrap();  // validated by types::check
+                positional[idx] = Some(self.compile_expr(value));
+            }
+            if events.is_none() || !template.has_events {
+                while let Some(&None) = positional.last() {
+                    positional.pop();
+                }
+            }
+            let mut args = positional.into_iter()
+                .map(|x| x.unwrap_or(Expression::Name(
+                    String::from("undefined"))))
+                .collect::<Vec<_>>();
+            if template.has_events {
+                args.extend(events.into_iter());
+            }
+            return Expression::Call(Box::new(self.compile_expr(func)), args);
+        }
+        let func = self.compile_expr(func);
+        if kwargs.len() == 0 && events.is_none() {
+            return Expression::Call(Box::new(func), args);
+        }
+        // Callee is used twice below, so unless it's a plain name it's bound
+        // to a temporary, for method calls the object is bound to keep `this`
+        let (func, bound) = match func {
+            Expression::Name(_) => (func, None),
+            Expression::Attr(obj, attr) => match *obj {
+                Expression::Name(_) => (Expression::Attr(obj, attr), None),
+                obj => (Expression::Attr(
+                            Box::new(Expression::Name(String::from("_self"))),
+                            attr),
+                        Some((String::from("_self"), obj))),
+            },
+            func => (Expression::Name(String::from("_func")),
+                     Some((String::from("_func"), func))),
+        };
+        let kw_entry = Expression::Attr(Box::new(func.clone()),
+                                        String::from("$kw"));
+        let kw_object = Expression::Object(kwargs.iter()
+            .map(|&(ref name, ref value)|
+                (name.clone(), self.compile_expr(value)))
+            .collect());
+        let kw_call = Expression::Call(Box::new(kw_entry.clone()),
+            vec![Expression::List(args.clone()), kw_object.clone()]
+            .into_iter().chain(events.clone().into_iter()).collect());
+        if kwargs.len() > 0 {
+            args.push(kw_object);
+        }
+        args.extend(events.into_iter());
+        let call = Expression::Ternary(
+            Box::new(kw_entry),
+            Box::new(kw_call),
+            Box::new(Expression::Call(Box::new(func), args)));
+        match bound {
+            Some((name, value)) => Expression::Call(
+                Box::new(Expression::Function(None,
+                    vec![Param { name: name, default_value: None }],
+                    vec![Statement::Return(call)])),
+                vec![value]),
+            None => call,
+        }
+    }
     fn compile_format(&self, items: &Vec<Fmt>) -> Expression
     {
         let mut exprs = items.iter().map(|e| match e {
@@ -118,44 +262,92 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 self.element(name, classes, attributes, key, body)
             }
             &Stmt::Format(ref value) => {
-                self.compile_format(value)
+                keyed(self.compile_format(value), key)
             }
             &Output(ref expr) => {
-                // TODO(tailhook) maybe put key somehow too?
-                self.compile_expr(expr)
+                keyed(self.compile_expr(expr), key)
             }
             &Condition(ref conditions, ref fallback) => {
-                // TODO(tailhook) maybe key should have if branch appended
                 conditions.iter().enumerate().rev()
                 .fold(fallback.as_ref()
                     .map(|x| self.fragment(x, key_num(&key, conditions.len())))
                     .unwrap_or(Expression::Str(String::new())),
                     |old, (idx, &(ref cond, ref value))| Expression::Ternary(
-                        // TODO(tailhook) maybe put key somehow too?
                         Box::new(self.compile_expr(cond)),
                         Box::new(self.fragment(value, key_num(&key, idx))),
                         Box::new(old),
                     ))
             }
-            &Stmt::ForOf(ref name, ref expr, ref subkey, ref body) => {
-                let expr = self.compile_expr(expr);
+            &Stmt::Match(ref value, ref cases, ref fallback) => {
+                // Value is evaluated once, so it's passed as an argument
+                let value_name = || Box::new(
+                    Expression::Name(String::from("_value")));
+                let result = cases.iter().enumerate().rev()
+                .fold(fallback.as_ref()
+                    .map(|x| self.fragment(x, key_num(&key, cases.len())))
+                    .unwrap_or(Expression::Str(String::new())),
+                    |old, (idx, &(ref values, ref body))| {
+                        let mut conds = values.iter().map(|v|
+                            Expression::Comparison(Comparator::Eq,
+                                value_name(),
+                                Box::new(self.compile_expr(v))));
+                        let first = conds.next().unwrap();
+                        Expression::Ternary(
+                            Box::new(conds.fold(first, |acc, c|
+                                Expression::Or(Box::new(acc), Box::new(c)))),
+                            Box::new(self.fragment(body, key_num(&key, idx))),
+                            Box::new(old))
+                    });
+                Expression::Call(
+                    Box::new(Expression::Function(None,
+                        vec![Param {
+                            name: String::from("_value"),
+                            default_value: None,
+                        }],
+                        vec![Statement::Return(result)])),
+                    vec![self.compile_expr(value)])
+            }
+            &Stmt::Try(ref body, ref name, ref fallback) => {
+                // Fragment is built inside the function, so exception
+                // replaces only this part of the tree
+                let name = name.as_ref().map(|x| self.ident(x))
+                    .unwrap_or(String::from("_err"));
+                let fallback = self.fragment(fallback, key_num(&key, 1));
+                self.catches.borrow_mut().push(
+                    (name.clone(), fallback.clone()));
+                let body = self.fragment(body, key_num(&key, 0));
+                self.catches.borrow_mut().pop();
                 Expression::Call(
-                    Box::new(Expression::Attr(
-                        Box::new(expr),
-                        String::from("map"))),
-                    vec![Expression::Function(None,
-                        vec![Param {name: name.clone(), default_value: None}],
-                        vec![Statement::Return(self.fragment(body,
-                            Some(subkey.as_ref().map(|x| self.compile_expr(&x))
-                                 .unwrap_or(key_join(key, name)))))]
-                    )])
+                    Box::new(Expression::Function(None, vec![], vec![
+                        Statement::Try(
+                            vec![Statement::Return(body)],
+                            name,
+                            vec![Statement::Return(fallback)]),
+                    ])),
+                    vec![])
+            }
+            &Stmt::ForOf { ref index, ref target, ref iterable,
+                           key: ref subkey, ref body, ref fallback } => {
+                self.iteration(index, target, iterable, subkey, body,
+                               fallback, key)
+            }
+            &Stmt::ForIn { ref name, ref value, ref object,
+                           key: ref subkey, ref body, ref fallback } => {
+                self.object_iteration(name, value, object, subkey, body,
+                                      fallback, key)
+            }
+            &Stmt::Call { ref name, ref arguments, ref keywords,
+                          ref links } => {
+                self.call(name, arguments, keywords, links, key)
             }
             &Stmt::Store(_, _) => unreachable!(),  // not an actual child
             &Stmt::Link(_) => unreachable!(),  // not an actual child
             &Stmt::Let(_, _) => unreachable!(),  // not an actual child
         }
     }
-    pub fn fragment(&self, statements: &Vec<html::Statement>,
+    // Builds nodes for statements, skipping variable declarations and links,
+    // which must be handled by the caller
+    pub fn nodes(&self, statements: &Vec<html::Statement>,
         key: Option<Expression>)
         -> Expression
     {
@@ -169,8 +361,13 @@ impl<'a, W:Write+'a> Generator<'a, W> {
             let mut obj = vec![(
                 String::from("children"),
                 Expression::List(
-                    stmt.iter()
-                    .map(|s| self.statement(s, None))
+                    stmt.iter().enumerate()
+                    .map(|(idx, s)| match *s {
+                        // Text has no state, so it's not wrapped to get a key
+                        &Stmt::Format(_) => self.statement(s, None),
+                        // Positional keys keep siblings of a branch stable
+                        _ => self.statement(s, key_num(&None, idx)),
+                    })
                     .collect())
                 )];
             if let Some(x) = key {
@@ -179,24 +376,158 @@ impl<'a, W:Write+'a> Generator<'a, W> {
             return Expression::Object(obj);
         }
     }
-    pub fn code(&self, ast: &Ast) -> Code {
+    pub fn fragment(&self, statements: &Vec<html::Statement>,
+        key: Option<Expression>)
+        -> Expression
+    {
+        let mut vars = vec![];
+        let mut stores = vec![];
+        let mut destroyed = vec![];
+        for st in statements.iter() {
+            match st {
+                &Stmt::Let(ref name, ref value) => {
+                    vars.push(Statement::Var(self.ident(name),
+                        self.compile_expr(value)));
+                }
+                &Stmt::Store(ref name, ref value) => {
+                    vars.push(self.store(name, value));
+                    stores.push((String::from("store_") + name,
+                                 Expression::Name(self.ident(name))));
+                    destroyed.push(self.store_destroyed(name));
+                }
+                _ => {}
+            }
+        }
+        if vars.len() == 0 {
+            return self.nodes(statements, key);
+        }
+        if stores.len() == 0 {
+            // Just a scope for variables
+            vars.push(Statement::Return(self.nodes(statements, key)));
+            return Expression::Call(
+                Box::new(Expression::Function(None, vec![], vars)),
+                vec![]);
+        }
+        let mut node = vec![
+            (String::from("children"), self.nodes(statements, None))];
+        node.extend(stores.into_iter());
+        let destroyed = if destroyed.len() == 1 {
+            destroyed.pop().unwrap()
+        } else {
+            Expression::List(destroyed)
+        };
+        node.push((String::from("events"), Expression::Object(vec![
+            (String::from("$destroyed"), destroyed)])));
+        vars.push(Statement::Return(Expression::Object(node)));
+        let func = self.lazy_node(vars);
+        if let Some(key) = key {
+            // Same as for elements, key must be known before old_node
+            return Expression::Object(vec![
+                (String::from("key"), key),
+                (String::from("children"), func),
+                ]);
+        } else {
+            return func;
+        }
+    }
+    // Cito calls the function when it knows `old_node`, i.e. after any
+    // enclosing try block has returned, so the handler is repeated inside
+    pub fn lazy_node(&self, mut body: Vec<Statement>) -> Expression {
+        if let Some(&(ref name, ref fallback)) = self.catches.borrow().last() {
+            body = vec![Statement::Try(body, name.clone(),
+                vec![Statement::Return(fallback.clone())])];
+        }
+        Expression::Function(None,
+            vec![Param {
+                name: String::from("old_node"),
+                default_value: None,
+            }],
+            body)
+    }
+    // Entry point for callers which don't know parameters at compile time:
+    // `name.$kw(positional_list, keywords_object[, events])`
+    fn kw_entry(&self, name: &String) -> Expression {
+        let template = &self.templates[name];
+        let mut params = vec![
+            Param { name: String::from("_args"), default_value: None },
+            Param { name: String::from("_kw"), default_value: None },
+        ];
+        let mut args = template.params.iter().enumerate().map(|(idx, p)| {
+            Expression::Ternary(
+                Box::new(Expression::Call(
+                    Box::new(Expression::Attr(
+                        Box::new(Expression::Name(String::from("_kw"))),
+                        String::from("hasOwnProperty"))),
+                    vec![Expression::Str(p.clone())])),
+                Box::new(Expression::Attr(
+                    Box::new(Expression::Name(String::from("_kw"))),
+                    p.clone())),
+                Box::new(Expression::Item(
+                    Box::new(Expression::Name(String::from("_args"))),
+                    Box::new(Expression::Num(format!("{}", idx))))))
+        }).collect::<Vec<_>>();
+        if template.has_events {
+            params.push(
+                Param { name: String::from("_events"), default_value: None });
+            args.push(Expression::Name(String::from("_events")));
+        }
+        Expression::Function(None, params, vec![
+            Statement::Return(Expression::Call(
+                Box::new(Expression::Name(self.ident(name))), args)),
+        ])
+    }
+
+    pub fn code(&mut self, ast: &Ast) -> Code {
         let mut stmt = vec!();
         for blk in ast.blocks.iter() {
-            if let &Block::Html {ref name, ref params,
-                ref statements, ..  } = blk
+            if let &Block::Const(ref name, ref value) = blk {
+                stmt.push(Statement::Var(self.ident(name),
+                                         self.compile_expr(value)));
+            }
+        }
+        for blk in ast.blocks.iter() {
+            if let &Block::Html {ref name, ref params, ref events,
+                ref statements } = blk
             {
-                stmt.push(Statement::Function(name.clone(),
-                    params.iter().map(|p| Param {
-                        name: p.name.clone(),
-                        default_value: p.default_value.as_ref().map(
-                            |v| Expression::Str(v.clone())),
-                    }).collect(),
-                    vec![
-                        Statement::Return(self.fragment(statements,
-                            Some(Expression::Str(format!("{}:{}",
-                                self.block_name, name))))),
-                    ]
-                ));
+                self.block_events = events.clone();
+                self.local_names = names::block_bindings(params, statements);
+                let mut args = params.iter().map(|p| Param {
+                    name: self.ident(&p.name),
+                    default_value: p.default_value.as_ref().map(
+                        |v| self.compile_expr(v)),
+                }).collect::<Vec<_>>();
+                if events.len() > 0 {
+                    args.push(Param {
+                        name: String::from("_events"),
+                        default_value: None,
+                    });
+                }
+                let mut body = vec![];
+                if self.debug {
+                    for p in params.iter() {
+                        if let Some(ref typ) = p.typ {
+                            body.push(self.type_check(name, &p.name,
+                                                      self.ident(&p.name),
+                                                      typ));
+                        }
+                    }
+                }
+                body.push(Statement::Return(self.fragment(statements,
+                    Some(Expression::Str(format!("{}:{}",
+                        self.block_name, name))))));
+                self.local_names.clear();
+                stmt.push(Statement::Function(self.ident(name), args, body));
+                // Calls within the module are resolved at compile time, so
+                // entry point is only needed for templates exported to other
+                // modules
+                if self.use_amd &&
+                    (self.templates[name].params.len() > 0 || events.len() > 0)
+                {
+                    stmt.push(Statement::Expr(Expression::AssignAttr(
+                        Box::new(Expression::Name(self.ident(name))),
+                        String::from("$kw"),
+                        Box::new(self.kw_entry(name)))));
+                }
             }
         }
         return Code {
@@ -1,36 +1,137 @@
 use std::io::{Write};
 
+use util::{Scoping, short_name};
+
 use super::Generator;
 
-use super::ast::{Code};
-use super::ast::Statement::{Var, Expr};
-use super::ast::Expression::{Call, Name, Str, Attr};
+use super::ast::{Code, Statement, Expression, Param, Comparator};
+use super::ast::Statement::{Var, Expr, If, Function, Return};
+use super::ast::Expression::{Call, Name, Str, Attr, Not, Assign, Object};
+use super::ast::Expression::{AssignAttr, And, Or, New, List, Comparison};
+
+
+fn name(x: &str) -> Expression {
+    Name(String::from(x))
+}
+
+fn attr(e: Expression, x: &str) -> Expression {
+    Attr(Box::new(e), String::from(x))
+}
+
+fn call(func: Expression, args: Vec<Expression>) -> Expression {
+    Call(Box::new(func), args)
+}
+
+fn and(a: Expression, b: Expression) -> Expression {
+    And(Box::new(a), Box::new(b))
+}
+
+fn set_attr(e: Expression, x: &str, value: Expression) -> Statement {
+    Expr(AssignAttr(Box::new(e), String::from(x), Box::new(value)))
+}
 
 
 impl<'a, W:Write+'a> Generator<'a, W> {
 
-    pub fn add_css(&self, code: Code, css: &str) -> Code {
-        let stmt = vec![
-            // var _style = document.createElement('style')
-            Var(String::from("_style"), Call(
-                    Box::new(Attr(Box::new(Name(String::from("document"))),
-                                  String::from("createElement"))),
-                    vec![Str(String::from("style"))])),
-            // _style.appendChild(document.createTextNode(css_text))
-            Expr(Call(
-                    Box::new(Attr(Box::new(Name(String::from("_style"))),
-                                  String::from("appendChild"))),
-                    vec![Call(
-                        Box::new(Attr(Box::new(Name(String::from("document"))),
-                                  String::from("createTextNode"))),
-                        vec![Str(css.to_string())])])),
-            // document.head.appendChild(_style)
-            Expr(Call(Box::new(
-                    Attr(Box::new(Attr(Box::new(Name(String::from("document"))),
-                                       String::from("head"))),
-                         String::from("appendChild"))),
-                    vec![Name(String::from("_style"))])),
+    // Id of the style element, so stylesheet is inserted once per root even
+    // if module is executed multiple times. Block name alone may be the same
+    // for files in different directories, so module name is hashed too
+    fn style_id(&self) -> String {
+        let name = self.block_name.chars().map(|c| match c {
+            'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => c,
+            _ => '_',
+        }).collect::<String>();
+        format!("marafet-css-{}-{}", name, short_name(self.amd_name))
+    }
+
+    // function _load_css(options), where options are:
+    // * root -- document (default) or shadow root to insert stylesheet to
+    // * nonce -- CSP nonce for the style element
+    // * adopt -- use constructable stylesheet if browser supports it
+    fn load_css_function(&self) -> Statement {
+        let id = self.style_id();
+        let adopted = vec![
+            Var(String::from("_sheet"),
+                New(Box::new(call(name("CSSStyleSheet"), vec![])))),
+            Expr(call(attr(name("_sheet"), "replaceSync"),
+                      vec![name("_css")])),
+            set_attr(name("_sheet"), "marafet_id", name("_id")),
+            // Sheet of previous execution of the module is replaced
+            set_attr(name("_root"), "adoptedStyleSheets", call(
+                attr(call(attr(attr(name("_root"), "adoptedStyleSheets"),
+                               "filter"),
+                          vec![Expression::Function(None, vec![Param {
+                                name: String::from("_item"),
+                                default_value: None,
+                            }], vec![
+                                Return(Comparison(Comparator::NotEq,
+                                    Box::new(attr(name("_item"),
+                                                  "marafet_id")),
+                                    Box::new(name("_id")))),
+                            ])]),
+                     "concat"),
+                vec![List(vec![name("_sheet")])])),
         ];
+        let element = vec![
+            Var(String::from("_parent"), Or(
+                Box::new(attr(name("_root"), "head")),
+                Box::new(name("_root")))),
+            Var(String::from("_style"), call(
+                attr(name("_parent"), "querySelector"),
+                vec![Str(format!("style#{}", id))])),
+            If(Not(Box::new(name("_style"))), vec![
+                Expr(Assign(Box::new(name("_style")),
+                    Box::new(call(attr(name("document"), "createElement"),
+                                  vec![Str(String::from("style"))])))),
+                set_attr(name("_style"), "id", name("_id")),
+                If(attr(name("_opts"), "nonce"), vec![
+                    Expr(call(attr(name("_style"), "setAttribute"), vec![
+                        Str(String::from("nonce")),
+                        attr(name("_opts"), "nonce"),
+                    ])),
+                ]),
+                Expr(call(attr(name("_parent"), "appendChild"),
+                          vec![name("_style")])),
+            ]),
+            set_attr(name("_style"), "textContent", name("_css")),
+        ];
+        Function(String::from("_load_css"), vec![Param {
+            name: String::from("options"),
+            default_value: None,
+        }], vec![
+            Var(String::from("_opts"), Or(
+                Box::new(name("options")),
+                Box::new(Object(vec![])))),
+            Var(String::from("_root"), Or(
+                Box::new(attr(name("_opts"), "root")),
+                Box::new(name("document")))),
+            Var(String::from("_id"), Str(id)),
+            Var(String::from("_adopt"), and(
+                and(attr(name("_opts"), "adopt"),
+                    attr(name("_root"), "adoptedStyleSheets")),
+                and(attr(name("window"), "CSSStyleSheet"),
+                    attr(attr(name("CSSStyleSheet"), "prototype"),
+                         "replaceSync")))),
+            If(name("_adopt"), adopted),
+            If(Not(Box::new(name("_adopt"))), element),
+        ])
+    }
+
+    pub fn add_css(&self, code: Code, css: &str) -> Code {
+        let mut stmt = vec![];
+        if self.export_css || self.auto_load_css {
+            stmt.push(Var(String::from("_css"), Str(css.to_string())));
+            stmt.push(self.load_css_function());
+        }
+        if self.auto_load_css {
+            let args = match self.css_nonce {
+                Some(nonce) => vec![Object(vec![
+                    (String::from("nonce"), Str(String::from(nonce))),
+                ])],
+                None => vec![],
+            };
+            stmt.push(Expr(call(name("_load_css"), args)));
+        }
         return Code {
             statements: stmt.into_iter()
                         .chain(code.statements.into_iter())
@@ -38,4 +139,24 @@ impl<'a, W:Write+'a> Generator<'a, W> {
         }
     }
 
+    // Values for `exports.css`, `exports.classes` and `exports.load_css`
+    pub fn css_exports(&self) -> Vec<(String, Expression)> {
+        if !self.export_css || self.css_text.is_none() {
+            return vec![];
+        }
+        let mut exports = vec![
+            (String::from("css"), name("_css")),
+        ];
+        if self.scoping != Scoping::BlockClass {
+            exports.push((String::from("classes"), Object(
+                self.class_map.iter()
+                .map(|&(ref name, ref scoped)| (name.clone(),
+                                                Str(scoped.clone())))
+                .collect())));
+        }
+        exports.push((String::from("load_css"),
+                      Name(String::from("_load_css"))));
+        exports
+    }
+
 }
@@ -1,9 +1,9 @@
 use std::io::Write;
-use std::collections::HashMap;
 
 use parser::html::Expression as Expr;
 use parser::html::Statement as Stmt;
 use parser::html::Statement::{Store};
+use parser::html::Link;
 use util::join;
 use super::Generator;
 use super::ast::{Statement, Param, Expression};
@@ -15,30 +15,115 @@ fn attr<'x, S: AsRef<str>>(e: Expression, v: S) -> Expression {
     E::Attr(Box::new(e), v.as_ref().to_string())
 }
 
+// Events are kept in source order, so that output is reproducible
+fn event_entry(events: &mut Vec<(String, Vec<Expression>)>, name: String)
+    -> &mut Vec<Expression>
+{
+    let idx = match events.iter().position(|&(ref k, _)| k == &name) {
+        Some(idx) => idx,
+        None => {
+            events.push((name, vec!()));
+            events.len() - 1
+        }
+    };
+    &mut events[idx].1
+}
+
 
 impl<'a, W:Write+'a> Generator<'a, W> {
 
-    fn compile_link(&self, expr: Expression,
+    fn compile_stream(&self, expr: Expression,
         filter: &Option<Expr>, map: Option<&Expr>)
         -> Expression
     {
         let mut e = expr;
         if let Some(map) = map {
-            let ev = Param { name: String::from("ev"), default_value: None };
+            let ev = Param {
+                name: self.ident(&String::from("ev")),
+                default_value: None,
+            };
             let func = E::Function(None, vec![ev], vec![
                 S::Return(self.compile_expr(map))]);
             e = E::Call(Box::new(attr(e, "map")), vec![func]);
         }
         if let &Some(ref filt) = filter {
-            let ev = Param { name: String::from("ev"), default_value: None };
+            let ev = Param {
+                name: self.ident(&String::from("ev")),
+                default_value: None,
+            };
             let func = E::Function(None, vec![ev], vec![
                 S::Return(self.compile_expr(filt))]);
             e = E::Call(Box::new(attr(e, "filter")), vec![func]);
         }
-        attr(e, "handle_event")
+        e
+    }
+
+    fn compile_link(&self, expr: Expression,
+        filter: &Option<Expr>, map: Option<&Expr>)
+        -> Expression
+    {
+        attr(self.compile_stream(expr, filter, map), "handle_event")
+    }
+
+    fn is_block_event(&self, dest: &Expr) -> bool {
+        match dest {
+            &Expr::Name(ref name) => self.block_events.contains(name),
+            _ => false,
+        }
+    }
+
+    // Events declared by the html block are streams passed by the caller
+    fn compile_dest(&self, dest: &Expr) -> Expression {
+        match dest {
+            &Expr::Name(ref name) if self.is_block_event(dest) => {
+                attr(E::Name(String::from("_events")), name)
+            }
+            _ => self.compile_expr(dest),
+        }
+    }
+
+    // Caller is not obliged to link every event, so we need a fallback
+    fn guard_event(&self, dest: &Expr, value: Expression, fallback: Expression)
+        -> Expression
+    {
+        if self.is_block_event(dest) {
+            E::Ternary(
+                Box::new(E::And(
+                    Box::new(E::Name(String::from("_events"))),
+                    Box::new(self.compile_dest(dest)))),
+                Box::new(value),
+                Box::new(fallback))
+        } else {
+            value
+        }
     }
 
 
+    // Stored value is kept in the node, so must be declared inside
+    // the function receiving `old_node`
+    pub fn store(&self, name: &String, value: &Expr) -> Statement {
+        let prop = String::from("store_") + name;
+        Statement::Var(self.ident(name),
+            E::Or(
+                Box::new(E::And(
+                    Box::new(E::Name(String::from("old_node"))),
+                    Box::new(E::Attr(Box::new(
+                            E::Name(String::from("old_node"))),
+                            prop)))),
+                Box::new(self.compile_expr(value))))
+    }
+
+    // Store is notified when the node owning it is removed
+    pub fn store_destroyed(&self, name: &String) -> Expression {
+        E::Ternary(
+            Box::new(attr(E::Name(self.ident(name)), "owner_destroyed")),
+            Box::new(attr(
+                attr(E::Name(self.ident(name)), "owner_destroyed"),
+                "handle_event")),
+            Box::new(E::Function(None, vec![], vec![])),
+        )
+    }
+
     pub fn element(&self, name: &String,
         classes: &Vec<(String, Option<Expr>)>,
         attributes: &Vec<(String, Expr)>,
@@ -53,64 +138,55 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 (String::from("tag"), Expression::Str(name.clone())),
         ];
         let mut statements = vec![];
-        let mut events = HashMap::new();
+        let mut events = Vec::new();
         for item in body.iter() {
             match item {
                 &Stmt::Let(ref name, ref value) => {
-                    statements.push(Statement::Var(name.clone(),
+                    statements.push(Statement::Var(self.ident(name),
                         self.compile_expr(value)));
                 }
                 &Store(ref name, ref value) => {
-                    let prop = String::from("store_") +name;
-                    statements.push(Statement::Var(name.clone(),
-                        E::Or(
-                            Box::new(E::And(
-                                Box::new(E::Name(String::from("old_node"))),
-                                Box::new(E::Attr(Box::new(
-                                        E::Name(String::from("old_node"))),
-                                        prop.clone())))),
-                            Box::new(self.compile_expr(value)))));
-                    properties.push((prop, E::Name(name.clone())));
-                    events.entry(String::from("$destroyed"))
-                    .or_insert(vec!()).push(
-                        E::Ternary(
-                            Box::new(attr(E::Name(name.clone()),
-                                "owner_destroyed")),
-                            Box::new(attr(
-                                attr(E::Name(name.clone()), "owner_destroyed"),
-                                "handle_event")),
-                            Box::new(E::Function(None, vec![], vec![])),
-                        ));
+                    statements.push(self.store(name, value));
+                    properties.push((String::from("store_") + name,
+                                     E::Name(self.ident(name))));
+                    event_entry(&mut events, String::from("$destroyed"))
+                        .push(self.store_destroyed(name));
                 }
                 &Stmt::Link(ref links) => {
                     for lnk in links {
                         match lnk {
                             &L::One(ref s, ref f, D::Stream(ref expr)) => {
-                                events.entry(s.clone()).or_insert(vec!()).push(
-                                    self.compile_link(
-                                        self.compile_expr(expr),
-                                        f, None));
+                                event_entry(&mut events, s.clone()).push(
+                                    self.guard_event(expr, self.compile_link(
+                                        self.compile_dest(expr), f, None),
+                                        E::Function(None, vec![], vec![])));
                             }
                             &L::Multi(ref names, D::Stream(ref expr)) => {
                                 let v = format!("_stream_{}",
                                     statements.len());
                                 statements.push(Statement::Var(
                                     v.clone(),
-                                    self.compile_expr(expr)));
+                                    self.guard_event(expr,
+                                        self.compile_dest(expr),
+                                        E::Name(String::from("undefined")))));
                                 for &(ref aname, ref flt, ref ename) in names {
                                     let ev = ename.as_ref()
                                              .unwrap_or(aname).clone();
-                                    events.entry(ev.clone()).or_insert(vec!())
-                                        .push(self.compile_link(
-                                            attr(E::Name(v.clone()), &aname),
-                                            flt, None));
+                                    event_entry(&mut events, ev.clone())
+                                        .push(self.guard_event(expr,
+                                            self.compile_link(
+                                                attr(E::Name(v.clone()),
+                                                     &aname),
+                                                flt, None),
+                                            E::Function(None, vec![], vec![])));
                                 }
                             }
                             &L::One(ref s, ref f, D::Mapping(ref val, ref dst))
                             => {
-                                events.entry(s.clone()).or_insert(vec!()).push(
-                                    self.compile_link(
-                                        self.compile_expr(dst), f, Some(val)));
+                                event_entry(&mut events, s.clone()).push(
+                                    self.guard_event(dst, self.compile_link(
+                                        self.compile_dest(dst), f, Some(val)),
+                                        E::Function(None, vec![], vec![])));
                             }
                             &L::Multi(ref names, D::Mapping(ref val, ref dest))
                             => {
@@ -118,14 +194,19 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                                     statements.len());
                                 statements.push(Statement::Var(
                                     v.clone(),
-                                    self.compile_expr(dest)));
+                                    self.guard_event(dest,
+                                        self.compile_dest(dest),
+                                        E::Name(String::from("undefined")))));
                                 for &(ref aname, ref flt, ref event) in names {
                                     let ename = event.as_ref()
                                              .unwrap_or(aname).clone();
-                                    events.entry(ename).or_insert(vec!()).push(
-                                        self.compile_link(
-                                            attr(E::Name(v.clone()), aname),
-                                            flt, Some(val)));
+                                    event_entry(&mut events, ename).push(
+                                        self.guard_event(dest,
+                                            self.compile_link(
+                                                attr(E::Name(v.clone()),
+                                                     aname),
+                                                flt, Some(val)),
+                                            E::Function(None, vec![], vec![])));
                                 }
                             }
                         }
@@ -134,14 +215,15 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 _ => {}
             }
         }
-        if classes.len() > 0 || attributes.len() > 0 {
+        if classes.len() > 0 || attributes.len() > 0 || self.is_styled(name)
+        {
             properties.push(
                 (String::from("attrs"),
                     self.attrs(name, classes, attributes)));
         }
         if body.len() > 0 {
             properties.push(
-                (String::from("children"), self.fragment(&body, None)));
+                (String::from("children"), self.nodes(&body, None)));
         }
         if events.len() > 0 {
             properties.push( (String::from("events"), Expression::Object(
@@ -157,12 +239,7 @@ impl<'a, W:Write+'a> Generator<'a, W> {
         if statements.len() > 0 {
             statements.push(
                 Statement::Return(Expression::Object(properties)));
-            let func = Expression::Function(None,
-                vec![Param {
-                    name: String::from("old_node"),
-                    default_value: None,
-                }],
-                statements);
+            let func = self.lazy_node(statements);
             if let Some(key) = key {
                 // If there is a key, we must turn function into fragment,
                 // because it's impossible to find out old_node before knowing
@@ -182,23 +259,96 @@ impl<'a, W:Write+'a> Generator<'a, W> {
         }
     }
 
+    pub fn call(&self, name: &String, arguments: &Vec<Expr>,
+        keywords: &Vec<(String, Expr)>, links: &Vec<Link>,
+        key: Option<Expression>)
+        -> Expression
+    {
+        use parser::html::Link as L;
+        use parser::html::LinkDest as D;
+
+        let mut events = vec![];
+        for lnk in links {
+            match lnk {
+                &L::One(ref s, ref f, D::Stream(ref expr)) => {
+                    events.push((s.clone(), self.guard_event(expr,
+                        self.compile_stream(self.compile_dest(expr), f, None),
+                        E::Name(String::from("undefined")))));
+                }
+                &L::One(ref s, ref f, D::Mapping(ref val, ref dst)) => {
+                    events.push((s.clone(), self.guard_event(dst,
+                        self.compile_stream(self.compile_dest(dst),
+                                            f, Some(val)),
+                        E::Name(String::from("undefined")))));
+                }
+                &L::Multi(ref names, D::Stream(ref expr)) => {
+                    for &(ref aname, ref flt, ref ename) in names {
+                        events.push((ename.as_ref().unwrap_or(aname).clone(),
+                            self.guard_event(expr, self.compile_stream(
+                                attr(self.compile_dest(expr), aname),
+                                flt, None),
+                            E::Name(String::from("undefined")))));
+                    }
+                }
+                &L::Multi(ref names, D::Mapping(ref val, ref dest)) => {
+                    for &(ref aname, ref flt, ref ename) in names {
+                        events.push((ename.as_ref().unwrap_or(aname).clone(),
+                            self.guard_event(dest, self.compile_stream(
+                                attr(self.compile_dest(dest), aname),
+                                flt, Some(val)),
+                            E::Name(String::from("undefined")))));
+                    }
+                }
+            }
+        }
+        let events = if events.len() > 0 {
+            Some(Expression::Object(events))
+        } else {
+            None
+        };
+        let call = self.compile_call(&Expr::Name(name.clone()),
+            arguments, keywords, events);
+        if let Some(key) = key {
+            return Expression::Object(vec![
+                ("key".to_string(), key),
+                ("children".to_string(), call),
+                ]);
+        } else {
+            return call;
+        }
+    }
+
+    // Element without classes needs the marker class too, if stylesheet
+    // has rules for its tag name, or rules which may match any element
+    fn is_styled(&self, name: &String) -> bool {
+        self.bare_element_names.contains(name) ||
+            self.bare_element_names.contains("*")
+    }
+
     fn attrs(&self, name: &String, cls: &Vec<(String, Option<Expr>)>,
         attrs: &Vec<(String, Expr)>)
         -> Expression
     {
         let mut class_literals = vec!();
         let mut class_expr = vec!();
-        if cls.len() > 0 || self.bare_element_names.contains(name) {
-            class_literals.push(self.block_name.to_string());
+        if cls.len() > 0 || self.is_styled(name) {
+            class_literals.push(self.scoping.marker(self.block_name));
         }
+        // Classes in `class` attribute are not renamed, as they are
+        // known only at runtime. Neither are classes which are global or
+        // not styled by this block at all
         for &(ref cname, ref opt_cond) in cls {
+            let cname = self.class_map.iter()
+                .find(|&&(ref name, _)| name == cname)
+                .map(|&(_, ref scoped)| scoped.clone())
+                .unwrap_or(cname.clone());
             if let &Some(ref cond) = opt_cond {
                 class_expr.push(Expression::Ternary(
                     Box::new(self.compile_expr(cond)),
-                    Box::new(Expression::Str(cname.clone())),
+                    Box::new(Expression::Str(cname)),
                     Box::new(Expression::Str(String::from("")))));
             } else {
-                class_literals.push(cname.clone());
+                class_literals.push(cname);
             }
         }
         let mut rattrs = vec!();
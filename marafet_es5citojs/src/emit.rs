@@ -4,7 +4,8 @@ use util::join;
 use parser::html::Comparator;
 
 use super::Generator;
-use super::ast::{Code, Statement, Expression};
+use super::ast::{Code, Statement, Expression, Param};
+use super::names::is_reserved;
 
 pub trait Emit {
     fn emit(&mut self, code: &Code) -> Result<()>;
@@ -32,14 +33,17 @@ fn is_ident<S: AsRef<str>>(s: S) -> bool {
     if s.len() == 0 {
         return false;
     }
+    if is_reserved(s) {
+        return false;
+    }
     let mut iter = s.chars();
     match iter.next().unwrap() {
-        'a'...'z'|'A'...'Z'|'_' => {},
+        'a'...'z'|'A'...'Z'|'_'|'$' => {},
         _ => return false,
     }
     for ch in iter {
         match ch {
-            'a'...'z'|'A'...'Z'|'0'...'9'|'_' => {}
+            'a'...'z'|'A'...'Z'|'0'...'9'|'_'|'$' => {}
             _ => return false,
         }
     }
@@ -54,6 +58,31 @@ impl<'a, W:Write+'a> Generator<'a, W> {
         }
         Ok(())
     }
+    fn emit_attr(&mut self, attr: &String) -> Result<()> {
+        if is_ident(attr) {
+            try!(write!(self.buf, ".{}", attr));
+        } else {
+            try!(self.buf.write_all(b"["));
+            try!(write_str(self.buf, attr));
+            try!(self.buf.write_all(b"]"));
+        }
+        Ok(())
+    }
+    fn emit_defaults(&mut self, params: &Vec<Param>, indent: u32)
+        -> Result<()>
+    {
+        let nindent = indent + self.indent;
+        for param in params.iter() {
+            if let Some(ref value) = param.default_value {
+                try!(self.write_indent(indent));
+                try!(write!(self.buf, "if ({name} === undefined) {name} = ",
+                    name=param.name));
+                try!(self.emit_expression(value, nindent));
+                try!(self.buf.write_all(b";\n"));
+            }
+        }
+        Ok(())
+    }
     fn emit_expression(&mut self, expr: &Expression, indent: u32)
         -> Result<()>
     {
@@ -114,7 +143,7 @@ impl<'a, W:Write+'a> Generator<'a, W> {
             }
             &Expression::Attr(ref parent, ref attr) => {
                 try!(self.emit_expression(parent, indent));
-                try!(write!(self.buf, ".{}", attr));
+                try!(self.emit_attr(attr));
             }
             &Expression::Item(ref parent, ref item) => {
                 try!(self.emit_expression(parent, indent));
@@ -139,9 +168,29 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 try!(self.emit_expression(val, indent));
             }
             &Expression::Not(ref val) => {
-                try!(write!(self.buf, "!"));
+                match **val {
+                    Expression::Name(_) | Expression::Attr(_, _)
+                    | Expression::Item(_, _) | Expression::Call(_, _)
+                    | Expression::Not(_) | Expression::Ternary(_, _, _) => {
+                        try!(write!(self.buf, "!"));
+                        try!(self.emit_expression(val, indent));
+                    }
+                    _ => {
+                        try!(write!(self.buf, "!("));
+                        try!(self.emit_expression(val, indent));
+                        try!(write!(self.buf, ")"));
+                    }
+                }
+            }
+            &Expression::TypeOf(ref val) => {
+                try!(write!(self.buf, "typeof "));
                 try!(self.emit_expression(val, indent));
             }
+            &Expression::InstanceOf(ref left, ref right) => {
+                try!(self.emit_expression(left, indent));
+                try!(write!(self.buf, " instanceof "));
+                try!(self.emit_expression(right, indent));
+            }
             &Expression::Or(ref left, ref right) => {
                 try!(self.emit_expression(left, indent));
                 try!(write!(self.buf, " || "));
@@ -188,14 +237,20 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                 try!(write!(self.buf, "function {name}({params}) {{\n",
                     name=name.as_ref().unwrap_or(&String::from("")),
                     params=join(params.iter().map(|x| &x.name), ", ")));
-                // TODO(tailhook) default values
+                try!(self.emit_defaults(params, nindent));
                 try!(self.emit_statements(&body, nindent));
                 try!(self.write_indent(indent));
                 try!(self.buf.write_all(b"}"));
             }
             &Expression::AssignAttr(ref expr, ref attr, ref value) => {
                 try!(self.emit_expression(expr, indent));
-                try!(write!(self.buf, ".{} = ", attr));
+                try!(self.emit_attr(attr));
+                try!(self.buf.write_all(b" = "));
+                try!(self.emit_expression(value, indent));
+            }
+            &Expression::Assign(ref target, ref value) => {
+                try!(self.emit_expression(target, indent));
+                try!(write!(self.buf, " = "));
                 try!(self.emit_expression(value, indent));
             }
             &Expression::Ternary(ref cond, ref left, ref right) => {
@@ -218,8 +273,8 @@ impl<'a, W:Write+'a> Generator<'a, W> {
             match stmt {
                 &Statement::Expr(ref expr) => {
                     try!(self.write_indent(indent));
-                    try!(self.emit_expression(expr, nindent));
-                    try!(self.buf.write_all(b"\n"));
+                    try!(self.emit_expression(expr, indent));
+                    try!(self.buf.write_all(b";\n"));
                 }
                 &Statement::Return(ref expr) => {
                     try!(self.write_indent(indent));
@@ -233,12 +288,51 @@ impl<'a, W:Write+'a> Generator<'a, W> {
                     try!(self.emit_expression(expr, nindent));
                     try!(self.buf.write_all(b";\n"));
                 }
+                &Statement::If(ref cond, ref body) => {
+                    try!(self.write_indent(indent));
+                    try!(self.buf.write_all(b"if ("));
+                    try!(self.emit_expression(cond, nindent));
+                    try!(self.buf.write_all(b") {\n"));
+                    try!(self.emit_statements(body, nindent));
+                    try!(self.write_indent(indent));
+                    try!(self.buf.write_all(b"}\n"));
+                }
+                &Statement::For(ref name, ref init, ref cond, ref step,
+                                ref body) => {
+                    try!(self.write_indent(indent));
+                    try!(write!(self.buf, "for (var {} = ", name));
+                    try!(self.emit_expression(init, nindent));
+                    try!(self.buf.write_all(b"; "));
+                    try!(self.emit_expression(cond, nindent));
+                    try!(self.buf.write_all(b"; "));
+                    try!(self.emit_expression(step, nindent));
+                    try!(self.buf.write_all(b") {\n"));
+                    try!(self.emit_statements(body, nindent));
+                    try!(self.write_indent(indent));
+                    try!(self.buf.write_all(b"}\n"));
+                }
+                &Statement::Throw(ref expr) => {
+                    try!(self.write_indent(indent));
+                    try!(self.buf.write_all(b"throw "));
+                    try!(self.emit_expression(expr, nindent));
+                    try!(self.buf.write_all(b";\n"));
+                }
+                &Statement::Try(ref body, ref name, ref fallback) => {
+                    try!(self.write_indent(indent));
+                    try!(self.buf.write_all(b"try {\n"));
+                    try!(self.emit_statements(body, nindent));
+                    try!(self.write_indent(indent));
+                    try!(write!(self.buf, "}} catch ({}) {{\n", name));
+                    try!(self.emit_statements(fallback, nindent));
+                    try!(self.write_indent(indent));
+                    try!(self.buf.write_all(b"}\n"));
+                }
                 &Statement::Function(ref name, ref params, ref body) => {
                     try!(self.write_indent(indent));
                     try!(write!(self.buf, "function {name}({params}) {{\n",
                         name=name,
                         params=join(params.iter().map(|x| &x.name), ", ")));
-                    // TODO(tailhook) default values
+                    try!(self.emit_defaults(params, nindent));
                     try!(self.emit_statements(&body, nindent));
                     try!(self.write_indent(indent));
                     try!(self.buf.write_all(b"}\n"));
@@ -0,0 +1,101 @@
+use std::collections::{HashMap, HashSet};
+
+use parser::{Ast, Block};
+use parser::html::{Statement, Link};
+
+
+fn check_links(declared: &HashMap<&String, &Vec<String>>,
+    name: &String, links: &Vec<Link>)
+    -> Result<(), String>
+{
+    let mut linked = HashSet::new();
+    for lnk in links.iter() {
+        let names = match lnk {
+            &Link::One(ref ev, _, _) => vec![ev],
+            &Link::Multi(ref items, _) => items.iter()
+                .map(|&(ref aname, _, ref ename)|
+                    ename.as_ref().unwrap_or(aname))
+                .collect(),
+        };
+        for ev in names.into_iter() {
+            if let Some(events) = declared.get(name) {
+                if !events.contains(ev) {
+                    return Err(format!(
+                        "Event {:?} is not declared in html block {:?}",
+                        ev, name));
+                }
+            }
+            if !linked.insert(ev) {
+                return Err(format!(
+                    "Event {:?} of {:?} is linked more than once",
+                    ev, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_statements(declared: &HashMap<&String, &Vec<String>>,
+    statements: &Vec<Statement>, in_element: bool)
+    -> Result<(), String>
+{
+    for st in statements.iter() {
+        match st {
+            &Statement::Element { ref body, .. } => {
+                try!(check_statements(declared, body, true));
+            }
+            &Statement::Condition(ref branches, ref fallback) => {
+                for &(_, ref body) in branches.iter() {
+                    try!(check_statements(declared, body, false));
+                }
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(declared, body, false));
+                }
+            }
+            &Statement::Match(_, ref cases, ref fallback) => {
+                for &(_, ref body) in cases.iter() {
+                    try!(check_statements(declared, body, false));
+                }
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(declared, body, false));
+                }
+            }
+            &Statement::Try(ref body, _, ref fallback) => {
+                try!(check_statements(declared, body, false));
+                try!(check_statements(declared, fallback, false));
+            }
+            &Statement::ForOf { ref body, ref fallback, .. }
+            | &Statement::ForIn { ref body, ref fallback, .. } => {
+                try!(check_statements(declared, body, false));
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(declared, body, false));
+                }
+            }
+            &Statement::Link(_) if !in_element => {
+                return Err(String::from(
+                    "Link is only allowed as a direct child of an element"));
+            }
+            &Statement::Call { ref name, ref links, .. } => {
+                try!(check_links(declared, name, links));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Only callees defined in the same file can be checked
+pub fn check(ast: &Ast) -> Result<(), String> {
+    let mut declared = HashMap::new();
+    for block in ast.blocks.iter() {
+        if let &Block::Html { ref name, ref events, .. } = block {
+            declared.insert(name, events);
+        }
+    }
+    for block in ast.blocks.iter() {
+        if let &Block::Html { ref statements, .. } = block {
+            try!(check_statements(&declared, statements, false));
+        }
+    }
+    Ok(())
+}
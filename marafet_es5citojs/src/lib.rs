@@ -1,10 +1,12 @@
 extern crate marafet_parser as parser;
 extern crate marafet_util as util;
 
-use std::io::{Write, Result};
-use std::collections::HashSet;
+use std::io::{Write, Result, Error, ErrorKind};
+use std::cell::RefCell;
+use std::collections::{HashSet, HashMap};
 
-use parser::{Ast};
+use parser::{Ast, Block};
+use util::Scoping;
 
 use emit::Emit;
 
@@ -15,35 +17,104 @@ mod code;
 mod css;
 mod amd;
 mod element;
+mod loops;
+mod events;
+mod types;
+mod names;
+mod resolve;
 
 
 pub struct Settings<'a> {
     pub block_name: &'a str,
+    // Must be the same as the one used for the stylesheet
+    pub scoping: Scoping,
     pub use_amd: bool,
     pub amd_name: &'a str,
+    // Stylesheet of the block, if it was generated
     pub css_text: Option<&'a str>,
+    // Insert the stylesheet into the document when module is executed
+    pub auto_load_css: bool,
+    // CSP nonce of the style element inserted by `auto_load_css`
+    pub css_nonce: Option<&'a str>,
+    // Export the stylesheet, class names and `load_css()` from AMD module
+    pub export_css: bool,
+    // Class names in css blocks and their names in the stylesheet
+    pub class_map: &'a [(String, String)],
+    pub debug: bool,
+    // Javascript globals which may be used in addition to default ones,
+    // if any are listed, other undefined names are errors
+    pub globals: &'a [String],
+}
+
+struct Template {
+    params: Vec<String>,
+    has_events: bool,
 }
 
 struct Generator<'a, W: 'a> {
     block_name: &'a str,
+    scoping: Scoping,
     indent: u32,
     bare_element_names: HashSet<String>,
     buf: &'a mut W,
     use_amd: bool,
     amd_name: &'a str,
     css_text: Option<&'a str>,
+    auto_load_css: bool,
+    css_nonce: Option<&'a str>,
+    export_css: bool,
+    class_map: &'a [(String, String)],
+    block_events: Vec<String>,
+    templates: HashMap<String, Template>,
+    module_names: HashSet<String>,
+    local_names: HashSet<String>,
+    // Error name and fallback node of the enclosing try blocks
+    catches: RefCell<Vec<(String, ast::Expression)>>,
+    debug: bool,
+}
+
+// Runs semantic checks, returns warnings
+pub fn check(ast: &Ast, settings: &Settings) -> Result<Vec<String>> {
+    try!(events::check(ast)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e)));
+    try!(types::check(ast)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e)));
+    resolve::check(ast, settings.globals)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))
 }
 
-pub fn generate<W>(buf: &mut W, ast: &Ast, settings: &Settings) -> Result<()>
+// Runs `check()` too, returns its warnings
+pub fn generate<W>(buf: &mut W, ast: &Ast, settings: &Settings)
+    -> Result<Vec<String>>
     where W: Write
 {
+    let warnings = try!(check(ast, settings));
     let mut gen = Generator {
         block_name: settings.block_name,
+        scoping: settings.scoping,
         use_amd: settings.use_amd,
         amd_name: settings.amd_name,
         css_text: settings.css_text,
+        auto_load_css: settings.auto_load_css,
+        css_nonce: settings.css_nonce,
+        export_css: settings.export_css && settings.use_amd,
+        class_map: settings.class_map,
+        debug: settings.debug,
         indent: 4,  // TODO(tailhook) allow customize
         bare_element_names: bare_elements::visitor(ast),
+        block_events: vec!(),
+        module_names: names::module_bindings(ast),
+        local_names: HashSet::new(),
+        catches: RefCell::new(vec!()),
+        templates: ast.blocks.iter().filter_map(|b| match b {
+            &Block::Html { ref name, ref params, ref events, .. } => {
+                Some((name.clone(), Template {
+                    params: params.iter().map(|p| p.name.clone()).collect(),
+                    has_events: events.len() > 0,
+                }))
+            }
+            _ => None,
+        }).collect(),
         buf: buf,
     };
     let mut code = gen.code(ast);
@@ -55,5 +126,5 @@ pub fn generate<W>(buf: &mut W, ast: &Ast, settings: &Settings) -> Result<()>
     }
     // TODO(tailhook) optimize
     try!(gen.emit(&code));
-    Ok(())
+    Ok(warnings)
 }
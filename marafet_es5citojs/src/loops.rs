@@ -0,0 +1,245 @@
+use std::io::Write;
+
+use parser::html::Expression as Expr;
+use parser::html::Statement as Stmt;
+use parser::html::{Target};
+
+use super::Generator;
+use super::code::key_join;
+use super::ast::{Statement, Param, Expression, Comparator};
+
+
+fn name(x: &str) -> Expression {
+    Expression::Name(String::from(x))
+}
+
+fn param(x: &String) -> Param {
+    Param { name: x.clone(), default_value: None }
+}
+
+fn attr(e: Expression, x: &str) -> Expression {
+    Expression::Attr(Box::new(e), String::from(x))
+}
+
+// Returns arguments of `range(start, end[, step])` if the loop is over range
+fn range_args(expr: &Expr) -> Option<&Vec<Expr>> {
+    match expr {
+        &Expr::Call(ref func, ref args, ref kwargs)
+        if args.len() >= 1 && args.len() <= 3 && kwargs.len() == 0 => {
+            match **func {
+                Expr::Name(ref name) if &name[..] == "range" => Some(args),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Returns `result` or `fallback` if the collection is empty
+fn or_empty(items: &str, result: Expression, fallback: Option<Expression>)
+    -> Expression
+{
+    match fallback {
+        Some(fallback) => Expression::Ternary(
+            Box::new(attr(name(items), "length")),
+            Box::new(result),
+            Box::new(fallback)),
+        None => result,
+    }
+}
+
+impl<'a, W:Write+'a> Generator<'a, W> {
+
+    // Declares variables for a target, returns name of the variable which
+    // must hold the whole value
+    fn unpack(&self, target: &Target, statements: &mut Vec<Statement>)
+        -> String
+    {
+        match target {
+            &Target::Name(ref name) => self.ident(name),
+            &Target::Destructure(ref names) => {
+                for name in names.iter() {
+                    statements.push(Statement::Var(self.ident(name),
+                        attr(self::name("_item"), name)));
+                }
+                String::from("_item")
+            }
+        }
+    }
+
+    fn empty_fragment(&self, fallback: &Option<Vec<Stmt>>,
+        key: &Option<Expression>)
+        -> Option<Expression>
+    {
+        fallback.as_ref().map(|body| self.fragment(body,
+            key.as_ref().map(|k| Expression::Add(
+                Box::new(k.clone()),
+                Box::new(Expression::Str(String::from(":else")))))))
+    }
+
+    // Function called for each item with item (or number) and index
+    fn loop_body(&self, index: &Option<String>, target: &Target,
+        subkey: &Option<Expr>, body: &Vec<Stmt>, key: Option<Expression>)
+        -> Expression
+    {
+        let mut statements = vec![];
+        let item_name = self.unpack(target, &mut statements);
+        let mut params = vec![param(&item_name)];
+        let index = match (subkey, target, index) {
+            (_, _, &Some(ref index)) => Some(self.ident(index)),
+            // There is no single value to use as a key, so position is used
+            (&None, &Target::Destructure(_), &None)
+            => Some(String::from("_index")),
+            _ => None,
+        };
+        let key = match (subkey, target) {
+            (&Some(ref subkey), _) => self.compile_expr(subkey),
+            (&None, &Target::Name(_)) => key_join(key, &item_name),
+            (&None, &Target::Destructure(_))
+            => key_join(key, index.as_ref().unwrap()),
+        };
+        if let Some(index) = index {
+            params.push(param(&index));
+        }
+        statements.push(Statement::Return(self.fragment(body, Some(key))));
+        Expression::Function(None, params, statements)
+    }
+
+    fn range_loop(&self, args: &Vec<Expr>, func: Expression,
+        fallback: Option<Expression>)
+        -> Expression
+    {
+        let (start, end, step) = match args.len() {
+            1 => (Expression::Num(String::from("0")),
+                  self.compile_expr(&args[0]), None),
+            2 => (self.compile_expr(&args[0]),
+                  self.compile_expr(&args[1]), None),
+            _ => (self.compile_expr(&args[0]),
+                  self.compile_expr(&args[1]), Some(&args[2])),
+        };
+        let mut statements = vec![
+            Statement::Var(String::from("_body"), func),
+            Statement::Var(String::from("_result"), Expression::List(vec![])),
+            Statement::Var(String::from("_end"), end),
+        ];
+        let less = Expression::Comparison(Comparator::Less,
+            Box::new(name("_i")), Box::new(name("_end")));
+        let (cond, step) = match step {
+            None => (less, Expression::Num(String::from("1"))),
+            Some(&Expr::Num(ref val)) if val.parse::<f64>() != Ok(0.) => {
+                (less, Expression::Num(val.clone()))
+            }
+            Some(step) => {
+                // Direction of the loop is only known at runtime
+                statements.push(Statement::Var(String::from("_step"),
+                    self.compile_expr(step)));
+                statements.push(Statement::If(
+                    Expression::Not(Box::new(name("_step"))), vec![
+                        Statement::Throw(Expression::New(Box::new(
+                            Expression::Call(Box::new(name("RangeError")),
+                                vec![Expression::Str(String::from(
+                                    "range() step must not be zero"))])))),
+                    ]));
+                (Expression::Ternary(
+                    Box::new(Expression::Comparison(Comparator::Greater,
+                        Box::new(name("_step")),
+                        Box::new(Expression::Num(String::from("0"))))),
+                    Box::new(less),
+                    Box::new(Expression::Comparison(Comparator::Greater,
+                        Box::new(name("_i")), Box::new(name("_end"))))),
+                 name("_step"))
+            }
+        };
+        statements.push(Statement::For(String::from("_i"), start, cond,
+            Expression::Assign(Box::new(name("_i")),
+                Box::new(Expression::Add(
+                    Box::new(name("_i")), Box::new(step)))),
+            vec![
+                Statement::Expr(Expression::Call(
+                    Box::new(attr(name("_result"), "push")),
+                    // Function is called for each item to capture variables
+                    vec![Expression::Call(Box::new(name("_body")), vec![
+                        name("_i"),
+                        attr(name("_result"), "length"),
+                        ])])),
+            ]));
+        statements.push(Statement::Return(
+            or_empty("_result", name("_result"), fallback)));
+        Expression::Call(
+            Box::new(Expression::Function(None, vec![], statements)),
+            vec![])
+    }
+
+    pub fn iteration(&self, index: &Option<String>, target: &Target,
+        iterable: &Expr, subkey: &Option<Expr>, body: &Vec<Stmt>,
+        fallback: &Option<Vec<Stmt>>, key: Option<Expression>)
+        -> Expression
+    {
+        let fallback = self.empty_fragment(fallback, &key);
+        let func = self.loop_body(index, target, subkey, body, key);
+        if let Some(args) = range_args(iterable) {
+            if !self.is_bound("range") {
+                return self.range_loop(args, func, fallback);
+            }
+        }
+        let items = self.compile_expr(iterable);
+        if fallback.is_none() {
+            return Expression::Call(Box::new(attr(items, "map")), vec![func]);
+        }
+        // Collection is evaluated once, so it's passed as an argument
+        Expression::Call(
+            Box::new(Expression::Function(None,
+                vec![param(&String::from("_items"))],
+                vec![Statement::Return(or_empty("_items",
+                    Expression::Call(
+                        Box::new(attr(name("_items"), "map")), vec![func]),
+                    fallback))])),
+            vec![items])
+    }
+
+    pub fn object_iteration(&self, key_name: &String, value: &Option<Target>,
+        object: &Expr, subkey: &Option<Expr>, body: &Vec<Stmt>,
+        fallback: &Option<Vec<Stmt>>, key: Option<Expression>)
+        -> Expression
+    {
+        let fallback = self.empty_fragment(fallback, &key);
+        let key_name = self.ident(key_name);
+        let mut statements = vec![];
+        if let &Some(ref target) = value {
+            let mut unpacked = vec![];
+            let value_name = self.unpack(target, &mut unpacked);
+            statements.push(Statement::Var(value_name,
+                Expression::Item(Box::new(name("_obj")),
+                    Box::new(Expression::Name(key_name.clone())))));
+            statements.extend(unpacked.into_iter());
+        }
+        let key = match subkey {
+            &Some(ref subkey) => self.compile_expr(subkey),
+            &None => key_join(key, &key_name),
+        };
+        statements.push(Statement::Return(self.fragment(body, Some(key))));
+        let func = Expression::Function(None, vec![param(&key_name)],
+                                        statements);
+        let keys = Expression::Call(Box::new(attr(name("Object"), "keys")),
+                                    vec![name("_obj")]);
+        let result = if fallback.is_some() {
+            vec![
+                Statement::Var(String::from("_keys"), keys),
+                Statement::Return(or_empty("_keys",
+                    Expression::Call(
+                        Box::new(attr(name("_keys"), "map")), vec![func]),
+                    fallback)),
+            ]
+        } else {
+            vec![
+                Statement::Return(Expression::Call(
+                    Box::new(attr(keys, "map")), vec![func])),
+            ]
+        };
+        // Object is evaluated once, so it's passed as an argument
+        Expression::Call(
+            Box::new(Expression::Function(None,
+                vec![param(&String::from("_obj"))], result)),
+            vec![self.compile_expr(object)])
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::Write;
+use std::collections::HashSet;
+
+use parser::{Ast, Block};
+use parser::html::{Statement, Param, Target};
+
+use super::Generator;
+
+
+// Words which can't be used as variable names in ES5
+const RESERVED: &'static [&'static str] = &[
+    "break", "case", "catch", "class", "const", "continue", "debugger",
+    "default", "delete", "do", "else", "enum", "export", "extends",
+    "finally", "for", "function", "if", "implements", "import", "in",
+    "instanceof", "interface", "let", "new", "package", "private",
+    "protected", "public", "return", "static", "super", "switch", "throw",
+    "try", "typeof", "var", "void", "while", "with", "yield",
+    ];
+
+// Names which generated code declares or relies on
+const GENERATED: &'static [&'static str] = &[
+    "old_node", "ev", "require", "exports", "_style", "_events",
+    "_args", "_kw", "_value", "_err", "_items", "_obj", "_keys", "_item",
+    "_body", "_result", "_end", "_step", "_i", "_css", "_load_css",
+    "_self", "_func", "_index",
+    "Array", "Math", "Object", "String", "TypeError", "RangeError",
+    "document", "undefined", "window", "CSSStyleSheet",
+    ];
+const GENERATED_PREFIXES: &'static [&'static str] = &["_stream_", "_mod_"];
+
+
+pub fn is_reserved(name: &str) -> bool {
+    RESERVED.contains(&name)
+}
+
+fn is_generated(name: &str) -> bool {
+    GENERATED.contains(&name) ||
+        GENERATED_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+fn bind_target(target: &Target, names: &mut HashSet<String>) {
+    match target {
+        &Target::Name(ref name) => {
+            names.insert(name.clone());
+        }
+        &Target::Destructure(ref items) => {
+            names.extend(items.iter().cloned());
+        }
+    }
+}
+
+fn bind_statements(statements: &Vec<Statement>, names: &mut HashSet<String>)
+{
+    for st in statements.iter() {
+        match st {
+            &Statement::Let(ref name, _) | &Statement::Store(ref name, _) => {
+                names.insert(name.clone());
+            }
+            &Statement::Element { ref body, .. } => {
+                bind_statements(body, names);
+            }
+            &Statement::Condition(ref branches, ref fallback) => {
+                for &(_, ref body) in branches.iter() {
+                    bind_statements(body, names);
+                }
+                if let &Some(ref body) = fallback {
+                    bind_statements(body, names);
+                }
+            }
+            &Statement::Match(_, ref cases, ref fallback) => {
+                for &(_, ref body) in cases.iter() {
+                    bind_statements(body, names);
+                }
+                if let &Some(ref body) = fallback {
+                    bind_statements(body, names);
+                }
+            }
+            &Statement::Try(ref body, ref name, ref fallback) => {
+                if let &Some(ref name) = name {
+                    names.insert(name.clone());
+                }
+                bind_statements(body, names);
+                bind_statements(fallback, names);
+            }
+            &Statement::ForOf { ref index, ref target, ref body,
+                                ref fallback, .. } => {
+                if let &Some(ref index) = index {
+                    names.insert(index.clone());
+                }
+                bind_target(target, names);
+                bind_statements(body, names);
+                if let &Some(ref body) = fallback {
+                    bind_statements(body, names);
+                }
+            }
+            &Statement::ForIn { ref name, ref value, ref body,
+                                ref fallback, .. } => {
+                names.insert(name.clone());
+                if let &Some(ref value) = value {
+                    bind_target(value, names);
+                }
+                bind_statements(body, names);
+                if let &Some(ref body) = fallback {
+                    bind_statements(body, names);
+                }
+            }
+            &Statement::Format(_) | &Statement::Output(_)
+            | &Statement::Link(_) | &Statement::Call { .. } => {}
+        }
+    }
+}
+
+// Names declared at the top level of the module
+pub fn module_bindings(ast: &Ast) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for block in ast.blocks.iter() {
+        match block {
+            &Block::Html { ref name, .. } | &Block::Const(ref name, _)
+            | &Block::ImportModule(ref name, _) => {
+                names.insert(name.clone());
+            }
+            &Block::ImportVars(ref items, _) => {
+                for &(ref name, ref alias) in items.iter() {
+                    names.insert(alias.as_ref().unwrap_or(name).clone());
+                }
+            }
+            &Block::Css(_, _, _) => {}
+        }
+    }
+    names
+}
+
+// Names declared by the html block, including all nested scopes
+pub fn block_bindings(params: &Vec<Param>, statements: &Vec<Statement>)
+    -> HashSet<String>
+{
+    let mut names = params.iter().map(|p| p.name.clone()).collect();
+    bind_statements(statements, &mut names);
+    names
+}
+
+impl<'a, W:Write+'a> Generator<'a, W> {
+    // Javascript name for the user's identifier. User variables which are
+    // reserved words or which would shadow (or be shadowed by) names used by
+    // generated code get a `$` suffix, which is never valid in the source
+    pub fn ident(&self, name: &String) -> String {
+        if is_reserved(name) || (is_generated(name) && self.is_bound(name)) {
+            format!("{}$", name)
+        } else {
+            name.clone()
+        }
+    }
+    // Whether the name is declared in the module, rather than a global
+    pub fn is_bound(&self, name: &str) -> bool {
+        self.module_names.contains(name) || self.local_names.contains(name)
+    }
+}
@@ -0,0 +1,399 @@
+use parser::{Ast, Block};
+use parser::html::{Statement, Expression, Fmt, Link, LinkDest, Param, Type};
+use parser::html::{Target};
+
+
+// Globals which are always available in a browser
+pub const DEFAULT_GLOBALS: &'static [&'static str] = &[
+    "window", "document", "console", "undefined", "true", "false", "null",
+    "String", "Number", "Boolean", "Object", "Array", "Math", "Date", "JSON",
+    "RegExp", "Error", "parseInt", "parseFloat", "isNaN", "isFinite",
+    "encodeURIComponent", "decodeURIComponent",
+    ];
+
+#[derive(PartialEq)]
+enum Kind {
+    Module,
+    Import,
+    Param,
+    Local,
+    Event,
+}
+
+struct Name<'a> {
+    name: &'a str,
+    kind: Kind,
+    used: bool,
+}
+
+struct Resolver<'a> {
+    globals: &'a [String],
+    scopes: Vec<Vec<Name<'a>>>,
+    block: Option<&'a String>,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+    // Constants which are not evaluated yet when module is executed
+    pending: Vec<&'a String>,
+}
+
+fn target_names(target: &Target) -> Vec<&String> {
+    match target {
+        &Target::Name(ref name) => vec![name],
+        &Target::Destructure(ref names) => names.iter().collect(),
+    }
+}
+
+impl<'a> Resolver<'a> {
+    fn push(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+    fn pop(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        for name in scope.iter().filter(|x| !x.used) {
+            match name.kind {
+                Kind::Import => self.warnings.push(format!(
+                    "Import {:?} is never used", name.name)),
+                Kind::Param => self.warnings.push(format!(
+                    "Parameter {:?} of {:?} is never used",
+                    name.name, self.block.unwrap())),
+                _ => {}
+            }
+        }
+    }
+    fn define(&mut self, name: &'a String, kind: Kind) {
+        if kind != Kind::Module && kind != Kind::Import {
+            let outer = self.scopes[..self.scopes.len()-1].iter()
+                .any(|s| s.iter().any(|x| x.name == &name[..]));
+            if outer {
+                self.warnings.push(format!(
+                    "{:?} in {:?} shadows a variable with the same name",
+                    name, self.block.unwrap()));
+            }
+        }
+        self.scopes.last_mut().unwrap().push(Name {
+            name: &name[..],
+            kind: kind,
+            used: false,
+        });
+    }
+    fn lookup(&mut self, name: &String) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(item) = scope.iter_mut().rev()
+                .find(|x| x.name == &name[..])
+            {
+                item.used = true;
+                if self.block.is_none() && self.pending.contains(&name) {
+                    self.errors.push(format!(
+                        "Constant {:?} is used before it's defined", name));
+                }
+                return;
+            }
+        }
+        if self.globals.contains(name) ||
+            DEFAULT_GLOBALS.contains(&&name[..])
+        {
+            return;
+        }
+        let msg = match self.block {
+            Some(block) => format!("Undefined name {:?} in {:?}", name, block),
+            None => format!("Undefined name {:?}", name),
+        };
+        // Without explicit list of globals the name may be a global which
+        // is not listed, so it's only a warning
+        if self.globals.len() > 0 {
+            self.errors.push(msg);
+        } else {
+            self.warnings.push(msg);
+        }
+    }
+    fn expr(&mut self, expr: &Expression) {
+        match expr {
+            &Expression::Name(ref name) => self.lookup(name),
+            &Expression::Str(_) | &Expression::Num(_) => {}
+            &Expression::Format(ref items) => self.format(items),
+            &Expression::New(ref a) | &Expression::Not(ref a)
+            | &Expression::Attr(ref a, _)
+            => self.expr(a),
+            &Expression::And(ref a, ref b) | &Expression::Or(ref a, ref b)
+            | &Expression::Mul(ref a, ref b) | &Expression::Div(ref a, ref b)
+            | &Expression::Add(ref a, ref b) | &Expression::Sub(ref a, ref b)
+            | &Expression::Comparison(_, ref a, ref b)
+            | &Expression::Item(ref a, ref b)
+            => {
+                self.expr(a);
+                self.expr(b);
+            }
+            &Expression::Call(ref func, ref args, ref kwargs) => {
+                self.expr(func);
+                for arg in args.iter()
+                    .chain(kwargs.iter().map(|&(_, ref x)| x))
+                {
+                    self.expr(arg);
+                }
+            }
+            &Expression::Dict(ref items) => {
+                for &(_, ref value) in items.iter() {
+                    self.expr(value);
+                }
+            }
+            &Expression::List(ref items) => {
+                for value in items.iter() {
+                    self.expr(value);
+                }
+            }
+        }
+    }
+    fn format(&mut self, items: &Vec<Fmt>) {
+        for item in items.iter() {
+            match item {
+                &Fmt::Raw(_) => {}
+                &Fmt::Str(ref e) | &Fmt::Int(ref e) | &Fmt::Float(ref e, _)
+                => self.expr(e),
+            }
+        }
+    }
+    fn is_defined(&self, name: &str) -> bool {
+        self.scopes.iter().any(|s| s.iter().any(|x| x.name == name))
+    }
+    // `range()` is not a function, but a loop syntax, unless it's redefined
+    fn iterable(&mut self, expr: &Expression) {
+        if let &Expression::Call(ref func, ref args, _) = expr {
+            if let Expression::Name(ref name) = **func {
+                if &name[..] == "range" && !self.is_defined(name) {
+                    for arg in args.iter() {
+                        self.expr(arg);
+                    }
+                    return;
+                }
+            }
+        }
+        self.expr(expr);
+    }
+    // Filters and mappings get the event as `ev`
+    fn event_expr(&mut self, expr: &Expression) {
+        self.push();
+        self.scopes.last_mut().unwrap().push(Name {
+            name: "ev",
+            kind: Kind::Local,
+            used: true,
+        });
+        self.expr(expr);
+        self.scopes.pop();
+    }
+    fn links(&mut self, links: &Vec<Link>) {
+        for lnk in links.iter() {
+            let (filters, dest) = match lnk {
+                &Link::One(_, ref filter, ref dest) => (vec![filter], dest),
+                &Link::Multi(ref names, ref dest)
+                => (names.iter().map(|&(_, ref f, _)| f).collect(), dest),
+            };
+            for filter in filters.into_iter() {
+                if let &Some(ref filter) = filter {
+                    self.event_expr(filter);
+                }
+            }
+            match dest {
+                &LinkDest::Stream(ref e) => self.expr(e),
+                &LinkDest::Mapping(ref value, ref e) => {
+                    self.event_expr(value);
+                    self.expr(e);
+                }
+            }
+        }
+    }
+    // Variables are visible in the whole statement list, like `var` in
+    // javascript
+    fn define_locals(&mut self, statements: &'a Vec<Statement>) {
+        for st in statements.iter() {
+            match st {
+                &Statement::Let(ref name, _) | &Statement::Store(ref name, _)
+                => self.define(name, Kind::Local),
+                _ => {}
+            }
+        }
+    }
+    fn scoped(&mut self, statements: &'a Vec<Statement>) {
+        self.push();
+        self.define_locals(statements);
+        self.statements(statements);
+        self.pop();
+    }
+    fn statements(&mut self, statements: &'a Vec<Statement>) {
+        for st in statements.iter() {
+            match st {
+                &Statement::Element { ref classes, ref attributes, ref body,
+                                      .. } => {
+                    self.push();
+                    self.define_locals(body);
+                    for &(_, ref cond) in classes.iter() {
+                        if let &Some(ref cond) = cond {
+                            self.expr(cond);
+                        }
+                    }
+                    for &(_, ref value) in attributes.iter() {
+                        self.expr(value);
+                    }
+                    self.statements(body);
+                    self.pop();
+                }
+                &Statement::Format(ref items) => self.format(items),
+                &Statement::Output(ref e) | &Statement::Store(_, ref e)
+                | &Statement::Let(_, ref e)
+                => self.expr(e),
+                &Statement::Link(ref links) => self.links(links),
+                &Statement::Condition(ref branches, ref fallback) => {
+                    for &(ref cond, ref body) in branches.iter() {
+                        self.expr(cond);
+                        self.scoped(body);
+                    }
+                    if let &Some(ref body) = fallback {
+                        self.scoped(body);
+                    }
+                }
+                &Statement::Match(ref value, ref cases, ref fallback) => {
+                    self.expr(value);
+                    for &(ref values, ref body) in cases.iter() {
+                        for value in values.iter() {
+                            self.expr(value);
+                        }
+                        self.scoped(body);
+                    }
+                    if let &Some(ref body) = fallback {
+                        self.scoped(body);
+                    }
+                }
+                &Statement::Try(ref body, ref name, ref fallback) => {
+                    self.scoped(body);
+                    self.push();
+                    if let &Some(ref name) = name {
+                        self.define(name, Kind::Local);
+                    }
+                    self.scoped(fallback);
+                    self.pop();
+                }
+                &Statement::ForOf { ref index, ref target, ref iterable,
+                                    ref key, ref body, ref fallback } => {
+                    self.iterable(iterable);
+                    self.push();
+                    if let &Some(ref index) = index {
+                        self.define(index, Kind::Local);
+                    }
+                    for name in target_names(target) {
+                        self.define(name, Kind::Local);
+                    }
+                    if let &Some(ref key) = key {
+                        self.expr(key);
+                    }
+                    self.scoped(body);
+                    self.pop();
+                    if let &Some(ref body) = fallback {
+                        self.scoped(body);
+                    }
+                }
+                &Statement::ForIn { ref name, ref value, ref object,
+                                    ref key, ref body, ref fallback } => {
+                    self.expr(object);
+                    self.push();
+                    self.define(name, Kind::Local);
+                    if let &Some(ref value) = value {
+                        for name in target_names(value) {
+                            self.define(name, Kind::Local);
+                        }
+                    }
+                    if let &Some(ref key) = key {
+                        self.expr(key);
+                    }
+                    self.scoped(body);
+                    self.pop();
+                    if let &Some(ref body) = fallback {
+                        self.scoped(body);
+                    }
+                }
+                &Statement::Call { ref name, ref arguments, ref keywords,
+                                   ref links } => {
+                    self.lookup(name);
+                    for arg in arguments.iter()
+                        .chain(keywords.iter().map(|&(_, ref x)| x))
+                    {
+                        self.expr(arg);
+                    }
+                    self.links(links);
+                }
+            }
+        }
+    }
+    fn params(&mut self, params: &'a Vec<Param>) {
+        for param in params.iter() {
+            if let Some(ref value) = param.default_value {
+                self.expr(value);
+            }
+            // Class is checked at runtime in debug mode
+            if let Some(Type::Class(ref cls)) = param.typ {
+                self.lookup(cls);
+            }
+        }
+        for param in params.iter() {
+            self.define(&param.name, Kind::Param);
+        }
+    }
+}
+
+// Returns list of warnings, names which can't be resolved are errors if
+// globals are listed explicitly and warnings otherwise
+pub fn check(ast: &Ast, globals: &[String]) -> Result<Vec<String>, String> {
+    let mut res = Resolver {
+        globals: globals,
+        scopes: vec![],
+        block: None,
+        warnings: vec![],
+        errors: vec![],
+        pending: vec![],
+    };
+    res.push();
+    for block in ast.blocks.iter() {
+        match block {
+            &Block::Html { ref name, .. } | &Block::Const(ref name, _) => {
+                res.define(name, Kind::Module);
+            }
+            &Block::ImportModule(ref name, _) => {
+                res.define(name, Kind::Import);
+            }
+            &Block::ImportVars(ref items, _) => {
+                for &(ref name, ref alias) in items.iter() {
+                    res.define(alias.as_ref().unwrap_or(name), Kind::Import);
+                }
+            }
+            &Block::Css(_, _, _) => {}
+        }
+    }
+    res.pending = ast.blocks.iter().filter_map(|b| match b {
+        &Block::Const(ref name, _) => Some(name),
+        _ => None,
+    }).collect();
+    for block in ast.blocks.iter() {
+        match block {
+            &Block::Const(ref name, ref value) => {
+                res.expr(value);
+                res.pending.retain(|x| x != &name);
+            }
+            &Block::Html { ref name, ref params, ref events, ref statements }
+            => {
+                res.block = Some(name);
+                res.push();
+                res.params(params);
+                for event in events.iter() {
+                    res.define(event, Kind::Event);
+                }
+                res.scoped(statements);
+                res.pop();
+                res.block = None;
+            }
+            _ => {}
+        }
+    }
+    res.pop();
+    if res.errors.len() > 0 {
+        return Err(res.errors.join("\n"));
+    }
+    Ok(res.warnings)
+}
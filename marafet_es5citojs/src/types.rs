@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use parser::{Ast, Block};
+use parser::html::{Statement, Expression, Param, Type, Fmt, Link, LinkDest};
+use parser::html::{Target};
+
+
+type Signatures<'a> = HashMap<&'a String, &'a Vec<Param>>;
+type Scope = HashMap<String, Type>;
+
+
+fn infer(expr: &Expression, scope: &Scope) -> Option<Type> {
+    match expr {
+        &Expression::Str(_) | &Expression::Format(_) => Some(Type::Str),
+        &Expression::Num(ref val) => {
+            if val.contains('.') {
+                Some(Type::Float)
+            } else {
+                Some(Type::Int)
+            }
+        }
+        &Expression::Name(ref name) => match &name[..] {
+            "true" | "false" => Some(Type::Bool),
+            _ => scope.get(name).cloned(),
+        },
+        &Expression::List(_) => Some(Type::List),
+        &Expression::Dict(_) => Some(Type::Dict),
+        &Expression::New(ref cls) => match **cls {
+            Expression::Name(ref name) => Some(Type::Class(name.clone())),
+            Expression::Call(ref cls, _, _) => match **cls {
+                Expression::Name(ref name) => Some(Type::Class(name.clone())),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn unbind(scope: &mut Scope, target: &Target) {
+    match target {
+        &Target::Name(ref name) => {
+            scope.remove(name);
+        }
+        &Target::Destructure(ref names) => {
+            for name in names.iter() {
+                scope.remove(name);
+            }
+        }
+    }
+}
+
+fn check_args(sig: &Signatures, scope: &Scope,
+    name: &String, args: &Vec<Expression>, kwargs: &Vec<(String, Expression)>)
+    -> Result<(), String>
+{
+    let params = if let Some(params) = sig.get(name) {
+        params
+    } else {
+        return Ok(());
+    };
+    if args.len() > params.len() {
+        return Err(format!("{:?} takes {} arguments, but {} are passed",
+            name, params.len(), args.len()));
+    }
+    let mut pairs = params.iter().zip(args.iter()).collect::<Vec<_>>();
+    for &(ref kw, ref arg) in kwargs.iter() {
+        let param = match params.iter().find(|p| &p.name == kw) {
+            Some(param) => param,
+            None => return Err(format!(
+                "{:?} has no parameter named {:?}", name, kw)),
+        };
+        if pairs.iter().any(|&(p, _)| &p.name == kw) {
+            return Err(format!(
+                "Parameter {:?} of {:?} is passed more than once", kw, name));
+        }
+        pairs.push((param, arg));
+    }
+    for (param, arg) in pairs.into_iter() {
+        if let (&Some(ref expected), Some(actual))
+            = (&param.typ, infer(arg, scope))
+        {
+            if !expected.accepts(&actual) {
+                return Err(format!(
+                    "Parameter {:?} of {:?} expects {}, but {} is passed",
+                    param.name, name, expected, actual));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_expr(sig: &Signatures, scope: &Scope, expr: &Expression)
+    -> Result<(), String>
+{
+    match expr {
+        &Expression::Name(_) | &Expression::Str(_) | &Expression::Num(_)
+        => {}
+        &Expression::Format(ref items) => {
+            try!(check_format(sig, scope, items));
+        }
+        &Expression::New(ref a) | &Expression::Not(ref a)
+        | &Expression::Attr(ref a, _)
+        => {
+            try!(check_expr(sig, scope, a));
+        }
+        &Expression::And(ref a, ref b) | &Expression::Or(ref a, ref b)
+        | &Expression::Mul(ref a, ref b) | &Expression::Div(ref a, ref b)
+        | &Expression::Add(ref a, ref b) | &Expression::Sub(ref a, ref b)
+        | &Expression::Comparison(_, ref a, ref b)
+        | &Expression::Item(ref a, ref b)
+        => {
+            try!(check_expr(sig, scope, a));
+            try!(check_expr(sig, scope, b));
+        }
+        &Expression::Call(ref func, ref args, ref kwargs) => {
+            if let Expression::Name(ref name) = **func {
+                try!(check_args(sig, scope, name, args, kwargs));
+            }
+            try!(check_expr(sig, scope, func));
+            for arg in args.iter().chain(kwargs.iter().map(|&(_, ref x)| x)) {
+                try!(check_expr(sig, scope, arg));
+            }
+        }
+        &Expression::Dict(ref items) => {
+            for &(_, ref value) in items.iter() {
+                try!(check_expr(sig, scope, value));
+            }
+        }
+        &Expression::List(ref items) => {
+            for value in items.iter() {
+                try!(check_expr(sig, scope, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_format(sig: &Signatures, scope: &Scope, items: &Vec<Fmt>)
+    -> Result<(), String>
+{
+    for item in items.iter() {
+        match item {
+            &Fmt::Raw(_) => {}
+            &Fmt::Str(ref e) | &Fmt::Int(ref e) | &Fmt::Float(ref e, _) => {
+                try!(check_expr(sig, scope, e));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_links(sig: &Signatures, scope: &Scope, links: &Vec<Link>)
+    -> Result<(), String>
+{
+    for lnk in links.iter() {
+        let (filters, dest) = match lnk {
+            &Link::One(_, ref filter, ref dest) => (vec![filter], dest),
+            &Link::Multi(ref names, ref dest)
+            => (names.iter().map(|&(_, ref f, _)| f).collect(), dest),
+        };
+        for filter in filters.into_iter() {
+            if let &Some(ref filter) = filter {
+                try!(check_expr(sig, scope, filter));
+            }
+        }
+        match dest {
+            &LinkDest::Stream(ref e) => {
+                try!(check_expr(sig, scope, e));
+            }
+            &LinkDest::Mapping(ref a, ref b) => {
+                try!(check_expr(sig, scope, a));
+                try!(check_expr(sig, scope, b));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_statements(sig: &Signatures, scope: &Scope,
+    statements: &Vec<Statement>)
+    -> Result<(), String>
+{
+    // Local variables shadow params, so we can't know their type
+    let mut scope = scope.clone();
+    for st in statements.iter() {
+        match st {
+            &Statement::Let(ref name, _) | &Statement::Store(ref name, _) => {
+                scope.remove(name);
+            }
+            _ => {}
+        }
+    }
+    for st in statements.iter() {
+        match st {
+            &Statement::Element { ref classes, ref attributes, ref body, .. }
+            => {
+                for &(_, ref cond) in classes.iter() {
+                    if let &Some(ref cond) = cond {
+                        try!(check_expr(sig, &scope, cond));
+                    }
+                }
+                for &(_, ref value) in attributes.iter() {
+                    try!(check_expr(sig, &scope, value));
+                }
+                try!(check_statements(sig, &scope, body));
+            }
+            &Statement::Format(ref items) => {
+                try!(check_format(sig, &scope, items));
+            }
+            &Statement::Output(ref e) | &Statement::Store(_, ref e)
+            | &Statement::Let(_, ref e)
+            => {
+                try!(check_expr(sig, &scope, e));
+            }
+            &Statement::Link(ref links) => {
+                try!(check_links(sig, &scope, links));
+            }
+            &Statement::Condition(ref branches, ref fallback) => {
+                for &(ref cond, ref body) in branches.iter() {
+                    try!(check_expr(sig, &scope, cond));
+                    try!(check_statements(sig, &scope, body));
+                }
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(sig, &scope, body));
+                }
+            }
+            &Statement::Match(ref value, ref cases, ref fallback) => {
+                try!(check_expr(sig, &scope, value));
+                for &(ref values, ref body) in cases.iter() {
+                    for value in values.iter() {
+                        try!(check_expr(sig, &scope, value));
+                    }
+                    try!(check_statements(sig, &scope, body));
+                }
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(sig, &scope, body));
+                }
+            }
+            &Statement::Try(ref body, ref name, ref fallback) => {
+                try!(check_statements(sig, &scope, body));
+                let mut inner = scope.clone();
+                if let &Some(ref name) = name {
+                    inner.remove(name);
+                }
+                try!(check_statements(sig, &inner, fallback));
+            }
+            &Statement::ForOf { ref index, ref target, ref iterable,
+                                ref key, ref body, ref fallback } => {
+                try!(check_expr(sig, &scope, iterable));
+                let mut inner = scope.clone();
+                if let &Some(ref index) = index {
+                    inner.remove(index);
+                }
+                unbind(&mut inner, target);
+                if let &Some(ref key) = key {
+                    try!(check_expr(sig, &inner, key));
+                }
+                try!(check_statements(sig, &inner, body));
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(sig, &scope, body));
+                }
+            }
+            &Statement::ForIn { ref name, ref value, ref object,
+                                ref key, ref body, ref fallback } => {
+                try!(check_expr(sig, &scope, object));
+                let mut inner = scope.clone();
+                inner.remove(name);
+                if let &Some(ref value) = value {
+                    unbind(&mut inner, value);
+                }
+                if let &Some(ref key) = key {
+                    try!(check_expr(sig, &inner, key));
+                }
+                try!(check_statements(sig, &inner, body));
+                if let &Some(ref body) = fallback {
+                    try!(check_statements(sig, &scope, body));
+                }
+            }
+            &Statement::Call { ref name, ref arguments, ref keywords,
+                               ref links } => {
+                try!(check_args(sig, &scope, name, arguments, keywords));
+                for arg in arguments.iter()
+                    .chain(keywords.iter().map(|&(_, ref x)| x))
+                {
+                    try!(check_expr(sig, &scope, arg));
+                }
+                try!(check_links(sig, &scope, links));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Only literals and typed params have known types at compile time
+pub fn check(ast: &Ast) -> Result<(), String> {
+    let mut signatures = HashMap::new();
+    let mut imports = HashSet::new();
+    for block in ast.blocks.iter() {
+        match block {
+            &Block::Html { ref name, ref params, .. } => {
+                signatures.insert(name, params);
+            }
+            &Block::ImportModule(ref name, _) => {
+                imports.insert(name);
+            }
+            &Block::ImportVars(ref items, _) => {
+                for &(ref name, ref alias) in items.iter() {
+                    imports.insert(alias.as_ref().unwrap_or(name));
+                }
+            }
+            _ => {}
+        }
+    }
+    for block in ast.blocks.iter() {
+        if let &Block::Html { ref name, ref params, ref statements, .. }
+            = block
+        {
+            let mut scope = HashMap::new();
+            for param in params.iter() {
+                // Class is checked by `instanceof`, so plain objects must
+                // be annotated as `dict` instead
+                if let Some(Type::Class(ref cls)) = param.typ {
+                    if !imports.contains(cls) {
+                        return Err(format!(
+                            "Type {:?} of parameter {:?} of {:?} must be \
+                             an imported class, use `dict` for plain objects",
+                            cls, param.name, name));
+                    }
+                }
+                if let Some(ref typ) = param.typ {
+                    if let Some(actual) = param.default_value.as_ref()
+                        .and_then(|x| infer(x, &HashMap::new()))
+                    {
+                        if !typ.accepts(&actual) {
+                            return Err(format!(
+                                "Default value of parameter {:?} of {:?} \
+                                 must be {}, but it's {}",
+                                param.name, name, typ, actual));
+                        }
+                    }
+                    scope.insert(param.name.clone(), typ.clone());
+                }
+            }
+            try!(check_statements(&signatures, &scope, statements));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,345 @@
+// Compiles `golden/<name>.mft` and compares the result with
+// `golden/<name>.js`, set MARAFET_BLESS=1 to rewrite the expected output
+extern crate marafet_parser as parser;
+extern crate marafet_util as util;
+extern crate marafet_es5citojs as es5citojs;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use util::Scoping;
+
+
+fn read(path: &str) -> String {
+    let mut buf = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut buf))
+        .expect(path);
+    buf
+}
+
+fn compile(source: &str, settings: &es5citojs::Settings)
+    -> Result<String, String>
+{
+    let ast = parser::parse_string(source).unwrap();
+    let mut buf = Vec::new();
+    try!(es5citojs::generate(&mut buf, &ast, settings)
+        .map_err(|e| e.to_string()));
+    Ok(String::from_utf8(buf).unwrap())
+}
+
+fn settings(block_name: &str) -> es5citojs::Settings {
+    es5citojs::Settings {
+        block_name: block_name,
+        scoping: Scoping::BlockClass,
+        use_amd: false,
+        amd_name: "",
+        css_text: None,
+        auto_load_css: false,
+        css_nonce: None,
+        export_css: false,
+        class_map: &[],
+        debug: false,
+        globals: &[],
+    }
+}
+
+fn check(name: &str, settings: &es5citojs::Settings) {
+    let source = read(&format!("tests/golden/{}.mft", name));
+    let result = compile(&source, settings).unwrap();
+    let path = format!("tests/golden/{}.js", name);
+    if env::var("MARAFET_BLESS").is_ok() {
+        File::create(&path).and_then(|mut f| f.write_all(result.as_bytes()))
+            .expect(&path);
+    } else if result != read(&path) {
+        panic!("Output of {}.mft differs, got:\n{}", name, result);
+    }
+}
+
+fn error(source: &str) -> String {
+    match compile(source, &settings("error")) {
+        Ok(_) => panic!("Compiled successfully: {:?}", source),
+        Err(e) => e,
+    }
+}
+
+fn warnings(source: &str, globals: &[String]) -> Vec<String> {
+    let ast = parser::parse_string(source).unwrap();
+    let mut settings = settings("warnings");
+    settings.globals = globals;
+    es5citojs::check(&ast, &settings).unwrap()
+}
+
+#[test]
+fn events() {
+    check("events", &settings("events"));
+}
+
+#[test]
+fn undeclared_event() {
+    let err = error("html outer():\n  inner()\n    link remove = 1\n\
+                     html inner() events delete:\n  \"x\"\n");
+    assert!(err.contains("\"remove\" is not declared"), "{}", err);
+}
+
+#[test]
+fn event_linked_twice() {
+    let err = error("html outer(x):\n  inner()\n    link delete = x\n\
+                     \x20   link delete = 1\n\
+                     html inner() events delete:\n  \"x\"\n");
+    assert!(err.contains("linked more than once"), "{}", err);
+}
+
+#[test]
+fn param_defaults() {
+    check("defaults", &settings("defaults"));
+}
+
+#[test]
+fn types_debug() {
+    let mut s = settings("types");
+    s.debug = true;
+    check("types", &s);
+}
+
+#[test]
+fn wrong_argument_type() {
+    let err = error("html outer(x: str):\n  inner(x)\n\
+                     html inner(index: int):\n  \"{index}\"\n");
+    assert!(err.contains("expects int, but str is passed"), "{}", err);
+    let err = error("html outer():\n  inner(\"1\")\n\
+                     html inner(index: int):\n  \"{index}\"\n");
+    assert!(err.contains("expects int, but str is passed"), "{}", err);
+}
+
+#[test]
+fn wrong_default_type() {
+    let err = error("html inner(index: int = \"1\"):\n  \"{index}\"\n");
+    assert!(err.contains("must be int, but it's str"), "{}", err);
+}
+
+#[test]
+fn keyword_arguments() {
+    check("kwargs", &settings("kwargs"));
+}
+
+#[test]
+fn wrong_arity() {
+    let err = error("html outer():\n  inner(1, 2)\n\
+                     html inner(a):\n  \"{a}\"\n");
+    assert!(err.contains("\"inner\" takes 1 arguments, but 2 are passed"),
+        "{}", err);
+}
+
+#[test]
+fn unknown_keyword() {
+    let err = error("html outer():\n  inner(b=1)\n\
+                     html inner(a):\n  \"{a}\"\n");
+    assert!(err.contains("\"inner\" has no parameter named \"b\""),
+        "{}", err);
+}
+
+#[test]
+fn duplicate_keyword() {
+    let err = error("html outer():\n  inner(1, a=2)\n\
+                     html inner(a):\n  \"{a}\"\n");
+    assert!(err.contains("\"a\" of \"inner\" is passed more than once"),
+        "{}", err);
+}
+
+#[test]
+fn let_and_store() {
+    check("let_store", &settings("let_store"));
+}
+
+#[test]
+fn consts() {
+    check("consts", &settings("consts"));
+}
+
+#[test]
+fn const_forward_reference() {
+    let err = error("const A = B + 1\nconst B = 1\n");
+    assert_eq!(err, "Constant \"B\" is used before it's defined");
+    let err = error("const A = [A]\n");
+    assert_eq!(err, "Constant \"A\" is used before it's defined");
+    // Templates are called after all constants are defined
+    compile("html foo():\n  \"{A}\"\nconst A = bar()\n\
+             html bar():\n  \"x\"\n", &settings("x")).unwrap();
+}
+
+#[test]
+fn loops() {
+    check("loops", &settings("loops"));
+}
+
+#[test]
+fn for_in_and_else() {
+    check("for_in", &settings("for_in"));
+}
+
+#[test]
+fn match_case() {
+    check("match", &settings("match"));
+}
+
+#[test]
+fn try_except() {
+    check("try", &settings("try"));
+}
+
+#[test]
+fn keys() {
+    check("keys", &settings("keys"));
+}
+
+#[test]
+fn event_order() {
+    check("event_order", &settings("event_order"));
+}
+
+#[test]
+fn deterministic() {
+    let source = read("tests/golden/event_order.mft");
+    let first = compile(&source, &settings("event_order")).unwrap();
+    for _ in 0..10 {
+        assert_eq!(compile(&source, &settings("event_order")).unwrap(),
+            first);
+    }
+}
+
+#[test]
+fn reserved_names() {
+    check("names", &settings("names"));
+}
+
+#[test]
+fn undefined_names() {
+    let source = "html foo(x):\n  \"{x} {y} {console.log}\"\n";
+    assert_eq!(warnings(source, &[]), vec![
+        String::from("Undefined name \"y\" in \"foo\""),
+    ]);
+    let globals = [String::from("y"), String::from("console")];
+    assert_eq!(warnings(source, &globals), Vec::<String>::new());
+    // With explicit globals, undefined names are errors
+    let ast = parser::parse_string(source).unwrap();
+    let mut s = settings("warnings");
+    let globals = [String::from("z")];
+    s.globals = &globals;
+    let err = es5citojs::check(&ast, &s).unwrap_err().to_string();
+    assert_eq!(err, "Undefined name \"y\" in \"foo\"");
+}
+
+#[test]
+fn unused_names() {
+    let source = "import {a, b} from 'mod'\nhtml foo(x, y):\n  \"{a} {x}\"\n";
+    assert_eq!(warnings(source, &[]), vec![
+        String::from("Parameter \"y\" of \"foo\" is never used"),
+        String::from("Import \"b\" is never used"),
+    ]);
+}
+
+#[test]
+fn shadowing() {
+    let source = "html foo(x, items):\n  \"{x}\"\n\
+                  \x20 for x of items:\n    \"{x}\"\n";
+    assert_eq!(warnings(source, &[]), vec![
+        String::from("\"x\" in \"foo\" shadows a variable with the same name"),
+    ]);
+}
+
+#[test]
+fn scoping() {
+    check("scoping", &settings("scoping"));
+    let mut s = settings("scoping_prefix");
+    s.scoping = Scoping::Prefix;
+    check("scoping_prefix", &s);
+    let mut s = settings("scoping_hash");
+    s.scoping = Scoping::Hash;
+    check("scoping_hash", &s);
+}
+
+#[test]
+fn amd_export_css() {
+    let class_map = [(String::from("box"), String::from("amd_css-box"))];
+    let mut s = settings("amd_css");
+    s.use_amd = true;
+    s.amd_name = "blocks/amd_css";
+    s.css_text = Some(".amd_css.amd_css-box {\n    color: red;\n}\n");
+    s.export_css = true;
+    s.scoping = Scoping::Prefix;
+    s.class_map = &class_map;
+    check("amd_css", &s);
+}
+
+#[test]
+fn auto_load_css() {
+    let mut s = settings("auto_load_css");
+    s.css_text = Some(".auto_load_css.box {\n    color: red;\n}\n");
+    s.auto_load_css = true;
+    check("auto_load_css", &s);
+}
+
+#[test]
+fn auto_load_css_nonce() {
+    let source = "html x():\n  .box\n";
+    let mut s = settings("box");
+    s.css_text = Some(".box.box {\n    color: red;\n}\n");
+    s.auto_load_css = true;
+    s.css_nonce = Some("r4nd0m");
+    s.amd_name = "a/box";
+    let first = compile(source, &s).unwrap();
+    assert!(first.contains("_load_css({nonce: \"r4nd0m\"})"), "{}", first);
+    // Blocks with the same name in different modules get own style element
+    s.amd_name = "b/box";
+    let second = compile(source, &s).unwrap();
+    let id = |js: &str| js.lines()
+        .find(|line| line.contains("marafet-css-box-"))
+        .map(String::from).unwrap();
+    assert!(id(&first) != id(&second));
+}
+
+#[test]
+fn params() {
+    check("params", &settings("params"));
+}
+
+#[test]
+fn try_lazy_nodes() {
+    check("try_lazy", &settings("try_lazy"));
+}
+
+#[test]
+fn class_map() {
+    let class_map = [(String::from("btn"), String::from("blk-btn"))];
+    let mut s = settings("blk");
+    s.scoping = Scoping::Prefix;
+    s.class_map = &class_map;
+    check("class_map", &s);
+}
+
+#[test]
+fn stores() {
+    check("stores", &settings("stores"));
+}
+
+#[test]
+fn multi_link_events() {
+    check("multi_link", &settings("multi_link"));
+}
+
+#[test]
+fn class_type_not_imported() {
+    let err = error("html foo(item: Item):\n  \"{item}\"\n");
+    assert!(err.contains("must be an imported class"), "{}", err);
+}
+
+#[test]
+fn id_selectors() {
+    check("id_selectors", &settings("id_selectors"));
+}
+
+#[test]
+fn attribute_selectors() {
+    check("attr_selectors", &settings("attr_selectors"));
+}
@@ -0,0 +1,50 @@
+define("blocks/amd_css", [
+    "require",
+    "exports",
+    "stores",
+], function (require, exports, _mod_stores) {
+    var Toggle = _mod_stores.Toggle;
+    var _css = ".amd_css.amd_css-box {\n    color: red;\n}\n";
+    function _load_css(options) {
+        var _opts = options || {};
+        var _root = _opts.root || document;
+        var _id = "marafet-css-amd_css-mvtq26e";
+        var _adopt = _opts.adopt && _root.adoptedStyleSheets && window.CSSStyleSheet && CSSStyleSheet.prototype.replaceSync;
+        if (_adopt) {
+            var _sheet = new CSSStyleSheet();
+            _sheet.replaceSync(_css);
+            _sheet.marafet_id = _id;
+            _root.adoptedStyleSheets = _root.adoptedStyleSheets.filter(function (_item) {
+                return _item.marafet_id !== _id;
+            }).concat([_sheet]);
+        }
+        if (!_adopt) {
+            var _parent = _root.head || _root;
+            var _style = _parent.querySelector("style#marafet-css-amd_css-mvtq26e");
+            if (!_style) {
+                _style = document.createElement("style");
+                _style.id = _id;
+                if (_opts.nonce) {
+                    _style.setAttribute("nonce", _opts.nonce);
+                }
+                _parent.appendChild(_style);
+            }
+            _style.textContent = _css;
+        }
+    }
+    function box(x) {
+        return {
+                tag: "div",
+                attrs: {"class": "amd_css amd_css-box"},
+                children: String(x),
+                key: "amd_css:box",
+            };
+    }
+    box.$kw = function (_args, _kw) {
+        return box(((_kw.hasOwnProperty("x"))?(_kw.x):(_args[0])));
+    };
+    exports.box = box;
+    exports.css = _css;
+    exports.classes = {box: "amd_css-box"};
+    exports.load_css = _load_css;
+});
@@ -0,0 +1,25 @@
+function form() {
+    return {
+            key: "attr_selectors:form",
+            children: [
+                {
+                    tag: "input",
+                    attrs: {
+                        type: "checkbox",
+                        "class": "attr_selectors",
+                    },
+                    key: ":0",
+                },
+                {
+                    tag: "a",
+                    attrs: {"class": "attr_selectors"},
+                    key: ":1",
+                },
+                {
+                    tag: "span",
+                    attrs: {"class": "attr_selectors"},
+                    key: ":2",
+                },
+            ],
+        };
+}
@@ -0,0 +1,37 @@
+var _css = ".auto_load_css.box {\n    color: red;\n}\n";
+function _load_css(options) {
+    var _opts = options || {};
+    var _root = _opts.root || document;
+    var _id = "marafet-css-auto_load_css-mztntfp";
+    var _adopt = _opts.adopt && _root.adoptedStyleSheets && window.CSSStyleSheet && CSSStyleSheet.prototype.replaceSync;
+    if (_adopt) {
+        var _sheet = new CSSStyleSheet();
+        _sheet.replaceSync(_css);
+        _sheet.marafet_id = _id;
+        _root.adoptedStyleSheets = _root.adoptedStyleSheets.filter(function (_item) {
+            return _item.marafet_id !== _id;
+        }).concat([_sheet]);
+    }
+    if (!_adopt) {
+        var _parent = _root.head || _root;
+        var _style = _parent.querySelector("style#marafet-css-auto_load_css-mztntfp");
+        if (!_style) {
+            _style = document.createElement("style");
+            _style.id = _id;
+            if (_opts.nonce) {
+                _style.setAttribute("nonce", _opts.nonce);
+            }
+            _parent.appendChild(_style);
+        }
+        _style.textContent = _css;
+    }
+}
+_load_css();
+function box(x) {
+    return {
+            tag: "div",
+            attrs: {"class": "auto_load_css box"},
+            children: String(x),
+            key: "auto_load_css:box",
+        };
+}
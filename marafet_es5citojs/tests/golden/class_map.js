@@ -0,0 +1,11 @@
+function main(active) {
+    return {
+            tag: "div",
+            attrs: {"class": "blk blk-btn icon"},
+            children: {
+                tag: "span",
+                attrs: {"class": "blk blk-btn" + " " + ((active)?("active"):(""))},
+            },
+            key: "blk:main",
+        };
+}
@@ -0,0 +1,22 @@
+var PAGE_SIZE = 20;
+var LABELS = {
+        next: "Next",
+        prev: "Previous",
+    };
+function pager(page) {
+    return {
+            key: "consts:pager",
+            children: [
+                {
+                    tag: "span",
+                    children: String(page) + " of " + String(PAGE_SIZE),
+                    key: ":0",
+                },
+                {
+                    tag: "button",
+                    children: String(LABELS.next),
+                    key: ":1",
+                },
+            ],
+        };
+}
@@ -0,0 +1,24 @@
+function card(title, size, tags, opts) {
+    if (title === undefined) title = "Untitled";
+    if (size === undefined) size = 2 * 8;
+    if (tags === undefined) tags = [
+            "a",
+            "b",
+        ];
+    if (opts === undefined) opts = {wide: true};
+    return {
+            key: "defaults:card",
+            children: [
+                {
+                    tag: "h1",
+                    children: String(title),
+                    key: ":0",
+                },
+                {
+                    tag: "span",
+                    children: String(size) + " " + String(tags.length) + " " + String(opts.wide),
+                    key: ":1",
+                },
+            ],
+        };
+}
@@ -0,0 +1,29 @@
+function buttons(handler) {
+    return {
+            key: "event_order:buttons",
+            children: function (old_node) {
+                var a = old_node && old_node.store_a || new Toggle();
+                var b = old_node && old_node.store_b || new Toggle();
+                var _stream_2 = a;
+                return {
+                        tag: "button",
+                        store_a: a,
+                        store_b: b,
+                        children: "go",
+                        events: {
+                            $destroyed: [
+                                ((a.owner_destroyed)?(a.owner_destroyed.handle_event):(function () {
+                                })),
+                                ((b.owner_destroyed)?(b.owner_destroyed.handle_event):(function () {
+                                })),
+                            ],
+                            click: handler.click.handle_event,
+                            mouseenter: _stream_2.mouseenter.handle_event,
+                            mouseleave: _stream_2.mouseleave.handle_event,
+                            keyup: handler.press.handle_event,
+                            dblclick: b.toggle.handle_event,
+                        },
+                    };
+            },
+        };
+}
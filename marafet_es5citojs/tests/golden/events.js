@@ -0,0 +1,43 @@
+function list(items, _events) {
+    return items.map(function (item) {
+            return {
+                    key: "events:list:" + item,
+                    children: row(item.label, item.id, undefined, {
+                        pick: ((_events && _events.select)?(_events.select.map(function (ev) {
+                            return item.id;
+                        })):(undefined)),
+                        "delete": ((_events && _events.remove)?(_events.remove.map(function (ev) {
+                            return item.id;
+                        })):(undefined)),
+                    }),
+                };
+        });
+}
+function row(label, id, extra, _events) {
+    return {
+            key: "events:row",
+            children: [
+                {
+                    tag: "span",
+                    children: String(label),
+                    key: ":0",
+                },
+                {
+                    tag: "button",
+                    children: "x",
+                    events: {click: ((_events && _events["delete"])?(_events["delete"].handle_event):(function () {
+                    }))},
+                    key: ":1",
+                },
+                {
+                    tag: "button",
+                    children: "pick",
+                    events: {click: ((_events && _events.pick)?(_events.pick.map(function (ev) {
+                        return id;
+                    }).handle_event):(function () {
+                    }))},
+                    key: ":2",
+                },
+            ],
+        };
+}
@@ -0,0 +1,58 @@
+function table(obj, items) {
+    return {
+            key: "for_in:table",
+            children: [
+                function (_obj) {
+                    return Object.keys(_obj).map(function (name) {
+                            return {
+                                    key: ":0:" + name,
+                                    children: String(name),
+                                };
+                        });
+                }(obj),
+                function (_obj) {
+                    return Object.keys(_obj).map(function (name) {
+                            var value = _obj[name];
+                            return {
+                                    key: name,
+                                    children: String(name) + "=" + String(value),
+                                };
+                        });
+                }(obj),
+                function (_obj) {
+                    return Object.keys(_obj).map(function (name) {
+                            var _item = _obj[name];
+                            var x = _item.x;
+                            var y = _item.y;
+                            return {
+                                    key: name,
+                                    children: String(name) + ": " + String(x) + ", " + String(y),
+                                };
+                        });
+                }(obj),
+                function (_items) {
+                    return ((_items.length)?(_items.map(function (item) {
+                            return {
+                                    key: ":3:" + item,
+                                    children: String(item),
+                                };
+                        })):({
+                            key: ":3" + ":else",
+                            children: "empty",
+                        }));
+                }(items),
+                function (_obj) {
+                    var _keys = Object.keys(_obj);
+                    return ((_keys.length)?(_keys.map(function (name) {
+                            return {
+                                    key: ":4:" + name,
+                                    children: String(name),
+                                };
+                        })):({
+                            key: ":4" + ":else",
+                            children: "no keys",
+                        }));
+                }(obj),
+            ],
+        };
+}
@@ -0,0 +1,15 @@
+function page() {
+    return {
+            tag: "div",
+            attrs: {
+                id: "main",
+                "class": "id_selectors",
+            },
+            children: {
+                tag: "span",
+                attrs: {"class": "id_selectors"},
+                children: "text",
+            },
+            key: "id_selectors:page",
+        };
+}
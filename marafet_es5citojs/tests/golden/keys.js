@@ -0,0 +1,36 @@
+function view(mode, items) {
+    return {
+            key: "keys:view",
+            children: [
+                {
+                    tag: "h1",
+                    children: "Title",
+                    key: ":0",
+                },
+                ((mode === "list")?({
+                    tag: "ul",
+                    children: "list",
+                    key: ":1:0",
+                }):(((mode === "grid")?({
+                    key: ":1:1",
+                    children: "grid",
+                }):({
+                    key: ":1:2",
+                    children: "none",
+                })))),
+                {
+                    key: ":2",
+                    children: items.length,
+                },
+                String(mode),
+                {
+                    tag: "div",
+                    children: ((mode)?({
+                        key: ":0",
+                        children: "mode",
+                    }):("")),
+                    key: ":4",
+                },
+            ],
+        };
+}
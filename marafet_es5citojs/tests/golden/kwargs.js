@@ -0,0 +1,43 @@
+function page(items, icons) {
+    return {
+            key: "kwargs:page",
+            children: [
+                {
+                    key: ":0",
+                    children: card("Title", items.length, "end"),
+                },
+                {
+                    key: ":1",
+                    children: card("Other"),
+                },
+                {
+                    key: ":2",
+                    children: ((icon.$kw)?(icon.$kw(["x"], {size: 16})):(icon("x", {size: 16}))),
+                },
+                String(((icons.big.$kw)?(icons.big.$kw([], {size: 24})):(icons.big({size: 24})))) + " " + String(function (_func) {
+                    return ((_func.$kw)?(_func.$kw([], {size: 16})):(_func({size: 16})));
+                }(icons.pick())) + " " + String(function (_self) {
+                    return ((_self.render.$kw)?(_self.render.$kw([], {size: 8})):(_self.render({size: 8})));
+                }(items[0])),
+            ],
+        };
+}
+function card(title, body, footer) {
+    if (body === undefined) body = "";
+    if (footer === undefined) footer = null;
+    return {
+            key: "kwargs:card",
+            children: [
+                {
+                    tag: "h1",
+                    children: String(title),
+                    key: ":0",
+                },
+                {
+                    tag: "p",
+                    children: String(body) + " " + String(footer),
+                    key: ":1",
+                },
+            ],
+        };
+}
@@ -0,0 +1,38 @@
+function panel(items, title) {
+    return {
+            key: "let_store:panel",
+            children: function (old_node) {
+                var heading = title + "!";
+                var open = old_node && old_node.store_open || new Toggle();
+                return {
+                        children: {children: [
+                            {
+                                tag: "h1",
+                                children: String(heading),
+                                key: ":0",
+                            },
+                            ((open.value)?(function () {
+                                var count = items.length;
+                                return {
+                                        key: ":1:0",
+                                        children: String(count) + " items",
+                                    };
+                            }()):("")),
+                            items.map(function (item) {
+                                return function () {
+                                        var label = item.name;
+                                        return {
+                                                tag: "span",
+                                                children: String(label),
+                                                key: ":2:" + item,
+                                            };
+                                    }();
+                            }),
+                        ]},
+                        store_open: open,
+                        events: {$destroyed: ((open.owner_destroyed)?(open.owner_destroyed.handle_event):(function () {
+                        }))},
+                    };
+            },
+        };
+}
@@ -0,0 +1,101 @@
+function foo(n, s, items) {
+    return {
+            key: "loops:foo",
+            children: [
+                function () {
+                    var _body = function (i) {
+                            return {
+                                    key: ":0:" + i,
+                                    children: String(i),
+                                };
+                        };
+                    var _result = [];
+                    var _end = n;
+                    for (var _i = 0; _i < _end; _i = _i + 1) {
+                        _result.push(_body(_i, _result.length));
+                    }
+                    return _result;
+                }(),
+                function () {
+                    var _body = function (i) {
+                            return {
+                                    key: ":1:" + i,
+                                    children: String(i),
+                                };
+                        };
+                    var _result = [];
+                    var _end = n;
+                    var _step = s;
+                    if (!_step) {
+                        throw new RangeError("range() step must not be zero");
+                    }
+                    for (var _i = 0; ((_step > 0)?(_i < _end):(_i > _end)); _i = _i + _step) {
+                        _result.push(_body(_i, _result.length));
+                    }
+                    return _result;
+                }(),
+                function () {
+                    var _body = function (i) {
+                            return {
+                                    key: ":2:" + i,
+                                    children: String(i),
+                                };
+                        };
+                    var _result = [];
+                    var _end = n;
+                    var _step = 0;
+                    if (!_step) {
+                        throw new RangeError("range() step must not be zero");
+                    }
+                    for (var _i = 0; ((_step > 0)?(_i < _end):(_i > _end)); _i = _i + _step) {
+                        _result.push(_body(_i, _result.length));
+                    }
+                    return _result;
+                }(),
+                items.map(function (item, idx) {
+                    return {
+                            key: ":3:" + item,
+                            children: String(idx) + ": " + String(item),
+                        };
+                }),
+                items.map(function (_item) {
+                    var id = _item.id;
+                    var title = _item.title;
+                    return {
+                            key: id,
+                            children: String(title),
+                        };
+                }),
+                items.map(function (_item, idx) {
+                    var id = _item.id;
+                    var title = _item.title;
+                    return {
+                            key: id,
+                            children: String(idx) + ": " + String(title),
+                        };
+                }),
+                items.map(function (_item, _index) {
+                    var title = _item.title;
+                    return {
+                            key: ":6:" + _index,
+                            children: String(title),
+                        };
+                }),
+                items.map(function (_item, idx) {
+                    var title = _item.title;
+                    return {
+                            key: ":7:" + idx,
+                            children: String(idx) + ": " + String(title),
+                        };
+                }),
+            ],
+        };
+}
+function bar(range, n) {
+    return range(n).map(function (i) {
+            return {
+                    key: "loops:bar:" + i,
+                    children: String(i),
+                };
+        });
+}
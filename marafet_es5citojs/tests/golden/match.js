@@ -0,0 +1,29 @@
+function status(state) {
+    return {
+            key: "match:status",
+            children: [
+                function (_value) {
+                    return ((_value === "ok")?({
+                            tag: "span",
+                            attrs: {"class": "match ok"},
+                            children: "Fine",
+                            key: ":0:0",
+                        }):(((_value === "warn" || _value === "error")?({
+                            tag: "span",
+                            attrs: {"class": "match bad"},
+                            children: String(state.message),
+                            key: ":0:1",
+                        }):({
+                            key: ":0:2",
+                            children: "Unknown",
+                        }))));
+                }(state.kind),
+                function (_value) {
+                    return ((_value === 1)?({
+                            key: ":1:0",
+                            children: "one",
+                        }):(""));
+                }(state.code),
+            ],
+        };
+}
@@ -0,0 +1,38 @@
+function row(item, _events) {
+    return {
+            key: "multi_link:row",
+            children: [
+                {
+                    key: ":0",
+                    children: function (old_node) {
+                        var _stream_0 = ((_events && _events["delete"])?(_events["delete"]):(undefined));
+                        return {
+                                tag: "button",
+                                children: {children: []},
+                                events: {
+                                    click: ((_events && _events["delete"])?(_stream_0.click.handle_event):(function () {
+                                    })),
+                                    dblclick: ((_events && _events["delete"])?(_stream_0.dblclick.handle_event):(function () {
+                                    })),
+                                },
+                            };
+                    },
+                },
+                {
+                    key: ":1",
+                    children: inner(item, {"delete": ((_events && _events.pick)?(_events.pick.remove.map(function (ev) {
+                        return item.id;
+                    })):(undefined))}),
+                },
+            ],
+        };
+}
+function inner(item, _events) {
+    return {
+            tag: "button",
+            children: {children: []},
+            events: {click: ((_events && _events["delete"])?(_events["delete"].handle_event):(function () {
+            }))},
+            key: "multi_link:inner",
+        };
+}
@@ -0,0 +1,21 @@
+function names(items, delete$) {
+    return function () {
+            var default$ = items.length;
+            var Math$ = delete$.value;
+            return {
+                    key: "names:names",
+                    children: [
+                        items.map(function (_i$) {
+                            return function () {
+                                    var _result$ = _i$.name;
+                                    return {
+                                            key: ":0:" + _i$,
+                                            children: String(_result$),
+                                        };
+                                }();
+                        }),
+                        String(default$) + " " + String(Math$),
+                    ],
+                };
+        }();
+}
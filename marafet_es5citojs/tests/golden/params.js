@@ -0,0 +1,28 @@
+function foo(ev$, class$, old_node$) {
+    if (old_node$ === undefined) old_node$ = "x";
+    return {
+            key: "params:foo",
+            children: function (old_node) {
+                var s = old_node && old_node.store_s || ev$.make();
+                return {
+                        tag: "div",
+                        store_s: s,
+                        children: {children: [
+                            String(ev$) + " " + String(class$) + " " + String(old_node$),
+                            {
+                                tag: "input",
+                                children: {children: []},
+                                events: {keyup: ev$.submit.map(function (ev$) {
+                                    return ev$.target.value;
+                                }).filter(function (ev$) {
+                                    return ev$.keyCode === 13;
+                                }).handle_event},
+                                key: ":1",
+                            },
+                        ]},
+                        events: {$destroyed: ((s.owner_destroyed)?(s.owner_destroyed.handle_event):(function () {
+                        }))},
+                    };
+            },
+        };
+}
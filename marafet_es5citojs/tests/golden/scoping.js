@@ -0,0 +1,17 @@
+function button(primary) {
+    return {
+            key: "scoping:button",
+            children: [
+                {
+                    tag: "a",
+                    key: ":0",
+                },
+                {
+                    tag: "button",
+                    attrs: {"class": "scoping button" + " " + ((primary)?("primary"):(""))},
+                    children: "ok",
+                    key: ":1",
+                },
+            ],
+        };
+}
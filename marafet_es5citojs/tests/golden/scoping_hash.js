@@ -0,0 +1,17 @@
+function button(primary) {
+    return {
+            key: "scoping_hash:button",
+            children: [
+                {
+                    tag: "a",
+                    key: ":0",
+                },
+                {
+                    tag: "button",
+                    attrs: {"class": "mr716hh button" + " " + ((primary)?("primary"):(""))},
+                    children: "ok",
+                    key: ":1",
+                },
+            ],
+        };
+}
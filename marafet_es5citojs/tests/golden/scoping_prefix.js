@@ -0,0 +1,17 @@
+function button(primary) {
+    return {
+            key: "scoping_prefix:button",
+            children: [
+                {
+                    tag: "a",
+                    key: ":0",
+                },
+                {
+                    tag: "button",
+                    attrs: {"class": "scoping_prefix button" + " " + ((primary)?("primary"):(""))},
+                    children: "ok",
+                    key: ":1",
+                },
+            ],
+        };
+}
@@ -0,0 +1,39 @@
+function foo(x) {
+    return {
+            key: "stores:foo",
+            children: [
+                x.map(function (i) {
+                    return {
+                            key: ":0:" + i,
+                            children: function (old_node) {
+                                var a = old_node && old_node.store_a || x.make();
+                                var b = old_node && old_node.store_b || x.make();
+                                return {
+                                        children: String(a),
+                                        store_a: a,
+                                        store_b: b,
+                                        events: {$destroyed: [
+                                            ((a.owner_destroyed)?(a.owner_destroyed.handle_event):(function () {
+                                            })),
+                                            ((b.owner_destroyed)?(b.owner_destroyed.handle_event):(function () {
+                                            })),
+                                        ]},
+                                    };
+                            },
+                        };
+                }),
+                ((x)?({
+                    key: ":1:0",
+                    children: function (old_node) {
+                        var c = old_node && old_node.store_c || x.make();
+                        return {
+                                children: String(c),
+                                store_c: c,
+                                events: {$destroyed: ((c.owner_destroyed)?(c.owner_destroyed.handle_event):(function () {
+                                }))},
+                            };
+                    },
+                }):("")),
+            ],
+        };
+}
@@ -0,0 +1,36 @@
+function profile(user) {
+    return {
+            key: "try:profile",
+            children: [
+                function () {
+                    try {
+                        return {
+                                tag: "h1",
+                                children: String(user.name.first),
+                                key: ":0:0",
+                            };
+                    } catch (_err) {
+                        return {
+                                key: ":0:1",
+                                children: "no name",
+                            };
+                    }
+                }(),
+                function () {
+                    try {
+                        return {
+                                key: ":1:0",
+                                children: String(user.age),
+                            };
+                    } catch (e) {
+                        return {
+                                tag: "span",
+                                attrs: {"class": "try error"},
+                                children: String(e.message),
+                                key: ":1:1",
+                            };
+                    }
+                }(),
+            ],
+        };
+}
@@ -0,0 +1,44 @@
+function foo(x) {
+    return function () {
+            try {
+                return {
+                        key: "try_lazy:foo:0",
+                        children: [
+                            {
+                                key: ":0",
+                                children: function (old_node) {
+                                    try {
+                                        var s = old_node && old_node.store_s || x.make();
+                                        return {
+                                                tag: "div",
+                                                store_s: s,
+                                                children: {
+                                                    tag: "span",
+                                                    children: "a",
+                                                },
+                                                events: {$destroyed: ((s.owner_destroyed)?(s.owner_destroyed.handle_event):(function () {
+                                                }))},
+                                            };
+                                    } catch (e) {
+                                        return {
+                                                key: "try_lazy:foo:1",
+                                                children: "failed",
+                                            };
+                                    }
+                                },
+                            },
+                            {
+                                tag: "p",
+                                children: String(x.y),
+                                key: ":1",
+                            },
+                        ],
+                    };
+            } catch (e) {
+                return {
+                        key: "try_lazy:foo:1",
+                        children: "failed",
+                    };
+            }
+        }();
+}
@@ -0,0 +1,44 @@
+function list(items, selected) {
+    if (selected === undefined) selected = 0 - 1;
+    if (!Array.isArray(items)) {
+        throw new TypeError("Parameter \"items\" of \"list\" must be list");
+    }
+    if (!(typeof selected === "number" && Math.floor(selected) === selected)) {
+        throw new TypeError("Parameter \"selected\" of \"list\" must be int");
+    }
+    return items.map(function (item) {
+            return {
+                    key: "types:list:" + item,
+                    children: row(item, 1, true),
+                };
+        });
+}
+function row(item, index, selected, ratio) {
+    if (selected === undefined) selected = false;
+    if (ratio === undefined) ratio = 1;
+    if (!(item instanceof Item)) {
+        throw new TypeError("Parameter \"item\" of \"row\" must be Item");
+    }
+    if (!(typeof index === "number" && Math.floor(index) === index)) {
+        throw new TypeError("Parameter \"index\" of \"row\" must be int");
+    }
+    if (!(typeof selected === "boolean")) {
+        throw new TypeError("Parameter \"selected\" of \"row\" must be bool");
+    }
+    if (!(typeof ratio === "number")) {
+        throw new TypeError("Parameter \"ratio\" of \"row\" must be float");
+    }
+    return {
+            key: "types:row",
+            children: String(index) + ": " + String(item.name),
+        };
+}
+function grid(cells) {
+    if (!(cells instanceof Array$)) {
+        throw new TypeError("Parameter \"cells\" of \"grid\" must be Array");
+    }
+    return {
+            key: "types:grid",
+            children: String(cells.length),
+        };
+}
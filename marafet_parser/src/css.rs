@@ -1,26 +1,85 @@
-use combine::{parser, Parser};
-use combine::combinator::{optional, ParserExt, sep_by, many};
-use combine::combinator::{between};
+use std::fmt;
+
+use combine::{parser, Parser, try};
+use combine::combinator::{optional, ParserExt, sep_by, sep_by1, many, many1};
+use combine::combinator::{between, satisfy};
+use combine::primitives::{Error, Info, SourcePosition};
 
 use super::Block;
 use super::token::{Token, ParseToken};
 use super::token::TokenType as Tok;
 use super::token::lift;
-use util::join;
 use super::{State, Result};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combinator {
+    Descendant,  // whitespace
+    Child,       // >
+    Adjacent,    // +
+    Sibling,     // ~
+}
+
 #[derive(Debug, Clone)]
-pub struct Selector {
+pub enum Simple {
+    Class(String),
+    Id(String),
+    // Operator includes `=`, value is kept quoted if it was in the source
+    Attribute(String, Option<(String, String)>),
+    // Arguments are kept as text, e.g. `2n+1` or `.x`
+    PseudoClass(String, Option<String>),
+    PseudoElement(String, Option<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Compound {
+    // Compound starts with `&` which refers to the selector of outer rule
+    pub parent: bool,
     pub element: Option<String>,
-    pub classes: Vec<String>,
-    pub state: Option<String>,
-    // TODO(tailhook) implement other selectors
+    pub simple: Vec<Simple>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pub first: Compound,
+    pub rest: Vec<(Combinator, Compound)>,
+}
+
+enum Part {
+    Parent,
+    Element(String),
+    Simple(Simple),
+    Combinator(Combinator),
+}
+
+type Span = (SourcePosition, SourcePosition);
+
 #[derive(Debug, Clone)]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub properties: Vec<(String, String)>,
+    pub nested: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AtRule {
+    // `@media` or `@supports`, condition and the body. Properties are only
+    // allowed when nested in a rule, and apply to the selector of the rule
+    Conditional(String, String, Vec<(String, String)>, Vec<Item>),
+    // Name and the list of frames, e.g. `from` or `0%, 50%`
+    Keyframes(String, Vec<(Vec<String>, Vec<(String, String)>)>),
+    FontFace(Vec<(String, String)>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Rule(Rule),
+    AtRule(AtRule),
+}
+
+enum RuleItem {
+    Property(String, String),
+    Rule(Rule),
+    AtRule(AtRule),
 }
 
 #[derive(Debug, Clone)]
@@ -29,76 +88,482 @@ pub struct Param {
     pub default_value: Option<String>,
 }
 
+// Default is a value like in declarations, e.g. `1px solid red`, until the
+// comma or closing paren. A single quoted string is unquoted
+fn param_value<'a>(input: State<'a>) -> Result<'a, String>
+{
+    many1::<Vec<_>, _>(
+        lift(Tok::OpenParen).and(parser(raw_tokens)).and(lift(Tok::CloseParen))
+        .map(|((open, mut inner), close)| {
+            inner.insert(0, open);
+            inner.push(close);
+            inner
+        })
+        .or(satisfy(|tok: Token<'a>| match tok.0 {
+            Tok::Comma | Tok::OpenParen | Tok::CloseParen | Tok::Newline
+            | Tok::Indent | Tok::Dedent | Tok::Eof => false,
+            _ => true,
+        }).map(|tok| vec![tok])))
+    .map(|groups| groups.into_iter().flat_map(|x| x.into_iter())
+                  .collect::<Vec<_>>())
+    .and_then(|mut tokens| {
+        if tokens.len() == 1 && tokens[0].0 == Tok::String {
+            return Ok(tokens.pop().unwrap().unescape());
+        }
+        match check_value(&tokens) {
+            Ok(()) => Ok(tokens_text(&tokens)),
+            Err(e) => Err(Error::Message(Info::Borrowed(e))),
+        }
+    })
+    .parse_state(input)
+}
+
 fn param<'a>(input: State<'a>) -> Result<'a, Param>
 {
-    lift(Tok::CssWord).and(optional(lift(Tok::Equals).with(lift(Tok::String))))
+    lift(Tok::CssWord).and(optional(lift(Tok::Equals).with(
+        parser(param_value))))
             .map(|(Token(_, name, _), opt)| Param {
                 name: String::from(name),
-                default_value: opt.map(|x| String::from(x.1)),
+                default_value: opt,
             })
     .parse_state(input)
 }
 
-#[allow(unconditional_recursion)]
+fn property_word<'a>(input: State<'a>) -> Result<'a, String>
+{
+    lift(Tok::CssWord).map(ParseToken::into_string)
+        .and(optional(
+            between(lift(Tok::OpenParen), lift(Tok::CloseParen),
+                parser(property_value))))
+    .map(|(word, opt_brackets)| {
+        if let Some(expr) = opt_brackets {
+            format!("{}({})", word, expr)
+        } else {
+            word
+        }
+    })
+    // Variables are kept as `$name` and substituted by the generator
+    .or(lift(Tok::CssVar).map(ParseToken::into_string))
+    .or(lift(Tok::CssHash).map(ParseToken::into_string))
+    .parse_state(input)
+}
+
+fn check_value(tokens: &Vec<Token>) -> ::std::result::Result<(), &'static str>
+{
+    for (idx, tok) in tokens.iter().enumerate() {
+        if tok.0 == Tok::Not {
+            match tokens.get(idx+1) {
+                Some(next) if next.1 == "important" && next.2 == end_of(tok)
+                    && idx + 2 == tokens.len() => {}
+                _ => return Err("`!` is only allowed as `!important` \
+                                 at the end of the value"),
+            }
+        }
+    }
+    Ok(())
+}
+
+// Value is kept as written, e.g. `12px/1.5 sans-serif` or `"x" !important`,
+// only the whitespace between tokens is normalized
 fn property_value<'a>(input: State<'a>) -> Result<'a, String>
 {
-    // TODO(tailhook) add numbers slashes and other things
+    parser(raw_tokens)
+    .and_then(|tokens| match check_value(&tokens) {
+        Ok(()) => Ok(tokens_text(&tokens)),
+        Err(e) => Err(Error::Message(Info::Borrowed(e))),
+    })
+    .parse_state(input)
+}
+
+impl fmt::Display for Combinator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Combinator::Descendant => write!(f, " "),
+            Combinator::Child => write!(f, " > "),
+            Combinator::Adjacent => write!(f, " + "),
+            Combinator::Sibling => write!(f, " ~ "),
+        }
+    }
+}
+
+impl fmt::Display for Simple {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Simple::Class(ref name) => write!(f, ".{}", name),
+            Simple::Id(ref name) => write!(f, "#{}", name),
+            Simple::Attribute(ref name, None) => write!(f, "[{}]", name),
+            Simple::Attribute(ref name, Some((ref op, ref value)))
+            => write!(f, "[{}{}{}]", name, op, value),
+            Simple::PseudoClass(ref name, None) => write!(f, ":{}", name),
+            Simple::PseudoClass(ref name, Some(ref arg))
+            => write!(f, ":{}({})", name, arg),
+            Simple::PseudoElement(ref name, None) => write!(f, "::{}", name),
+            Simple::PseudoElement(ref name, Some(ref arg))
+            => write!(f, "::{}({})", name, arg),
+        }
+    }
+}
+
+impl Simple {
+    // `:global(...)` escapes from scoping
+    pub fn is_global(&self) -> bool {
+        match *self {
+            Simple::PseudoClass(ref name, Some(_)) => name == "global",
+            _ => false,
+        }
+    }
+}
+
+impl Selector {
+    pub fn compounds(&self) -> Vec<&Compound> {
+        Some(&self.first).into_iter()
+            .chain(self.rest.iter().map(|&(_, ref c)| c))
+            .collect()
+    }
+    pub fn has_parent(&self) -> bool {
+        self.compounds().iter().any(|c| c.parent)
+    }
+}
+
+fn end_of(tok: &Token) -> SourcePosition {
+    SourcePosition {
+        line: tok.2.line,
+        column: tok.2.column + tok.1.chars().count() as i32,
+    }
+}
+
+// Words in css blocks include dots, so `div.x.y` is a single token
+fn word_parts(word: &str, after_dot: bool) -> Vec<Part> {
+    let mut names = word.split('.');
+    let mut parts = vec![];
+    if !after_dot {
+        parts.push(Part::Element(String::from(names.next().unwrap())));
+    }
+    parts.extend(names.map(|x| Part::Simple(Simple::Class(String::from(x)))));
+    parts
+}
+
+// Tokens are joined back with a space only where there was one
+fn tokens_text(tokens: &Vec<Token>) -> String {
+    let mut res = String::new();
+    let mut prev_end = None;
+    for tok in tokens.iter() {
+        if prev_end.is_some() && prev_end != Some(tok.2) {
+            res.push(' ');
+        }
+        res.push_str(tok.1);
+        prev_end = Some(end_of(tok));
+    }
+    res
+}
+
+fn raw_tokens<'a>(input: State<'a>) -> Result<'a, Vec<Token<'a>>>
+{
     many::<Vec<_>, _>(
-        lift(Tok::CssWord).map(ParseToken::into_string)
-            .and(optional(
-                between(lift(Tok::OpenParen), lift(Tok::CloseParen),
-                    parser(property_value))))
-        .map(|(word, opt_brackets)| {
-            if let Some(expr) = opt_brackets {
-                format!("{}({})", word, expr)
-            } else {
-                word
-            }
-        }).or(lift(Tok::Comma).map(ParseToken::into_string))
-    ).map(|names| join(names.into_iter(), " "))
+        lift(Tok::OpenParen).and(parser(raw_tokens)).and(lift(Tok::CloseParen))
+        .map(|((open, mut inner), close)| {
+            inner.insert(0, open);
+            inner.push(close);
+            inner
+        })
+        .or(satisfy(|tok: Token<'a>| match tok.0 {
+            Tok::OpenParen | Tok::CloseParen | Tok::Newline
+            | Tok::Indent | Tok::Dedent | Tok::Eof => false,
+            _ => true,
+        }).map(|tok| vec![tok])))
+    .map(|groups| groups.into_iter().flat_map(|x| x.into_iter()).collect())
     .parse_state(input)
 }
 
+fn attribute<'a>(input: State<'a>) -> Result<'a, (Vec<Part>, Span)>
+{
+    lift(Tok::OpenBracket)
+    .and(lift(Tok::CssWord))
+    .and(optional(
+        optional(lift(Tok::CssSymbol).or(lift(Tok::Multiply)))
+        .skip(lift(Tok::Equals))
+        .and(lift(Tok::CssWord).or(lift(Tok::String)))))
+    .and(lift(Tok::CloseBracket))
+    .map(|(((open, name), value), close)| {
+        let value = value.map(|(op, value)| (
+            format!("{}=", op.map(|x| x.1).unwrap_or("")),
+            String::from(value.1)));
+        (vec![Part::Simple(Simple::Attribute(String::from(name.1), value))],
+         (open.2, end_of(&close)))
+    })
+    .parse_state(input)
+}
+
+fn pseudo<'a>(input: State<'a>) -> Result<'a, (Vec<Part>, Span)>
+{
+    lift(Tok::Colon)
+    .and(optional(lift(Tok::Colon)))
+    .and(lift(Tok::CssWord))
+    .and(optional(lift(Tok::OpenParen).with(parser(raw_tokens))
+                  .and(lift(Tok::CloseParen))))
+    .and_then(|(((colon, double), name), arg)| {
+        if name.1 == "global" && (arg.is_none() || double.is_some()) {
+            return Err(Error::Message(Info::Borrowed(
+                "`:global` requires a selector in parenthesis")));
+        }
+        let end = arg.as_ref().map(|&(_, ref close)| end_of(close))
+            .unwrap_or(end_of(&name));
+        let name = String::from(name.1);
+        let arg = arg.map(|(tokens, _)| tokens_text(&tokens));
+        let simple = if double.is_some() {
+            Simple::PseudoElement(name, arg)
+        } else {
+            Simple::PseudoClass(name, arg)
+        };
+        Ok((vec![Part::Simple(simple)], (colon.2, end)))
+    })
+    .parse_state(input)
+}
+
+// Combinators and `&`
+fn symbol<'a>(input: State<'a>) -> Result<'a, (Vec<Part>, Span)>
+{
+    lift(Tok::Greater).map(|tok| (Part::Combinator(Combinator::Child), tok))
+    .or(lift(Tok::Plus)
+        .map(|tok| (Part::Combinator(Combinator::Adjacent), tok)))
+    .or(lift(Tok::CssSymbol).and_then(|tok| match tok.1 {
+            "~" => Ok((Part::Combinator(Combinator::Sibling), tok)),
+            "&" => Ok((Part::Parent, tok)),
+            _ => Err(Error::Message(Info::Borrowed(
+                "unexpected symbol in selector"))),
+        }))
+    .map(|(part, tok)| (vec![part], (tok.2, end_of(&tok))))
+    .parse_state(input)
+}
+
+fn selector_item<'a>(input: State<'a>) -> Result<'a, (Vec<Part>, Span)>
+{
+    lift(Tok::CssWord)
+        .map(|tok| (word_parts(tok.1, false), (tok.2, end_of(&tok))))
+    .or(lift(Tok::Multiply)
+        .map(|tok| (vec![Part::Element(String::from("*"))],
+                    (tok.2, end_of(&tok)))))
+    .or(lift(Tok::Dot).and(lift(Tok::CssWord))
+        .map(|(dot, word)| (word_parts(word.1, true),
+                            (dot.2, end_of(&word)))))
+    .or(lift(Tok::CssHash)
+        .map(|tok| (vec![Part::Simple(Simple::Id(String::from(&tok.1[1..])))],
+                    (tok.2, end_of(&tok)))))
+    .or(parser(attribute))
+    .or(parser(pseudo))
+    .or(parser(symbol))
+    .parse_state(input)
+}
+
+// Whitespace isn't tokenized in css blocks, so parts of the selector which
+// don't touch each other are joined by the descendant combinator. Selector
+// starting with a combinator is relative to the outer rule
+fn build_selector(items: Vec<(Vec<Part>, Span)>)
+    -> ::std::result::Result<Selector, &'static str>
+{
+    let mut compounds: Vec<(Combinator, Compound)> = vec![];
+    let mut pending = None;
+    let mut prev_end = None;
+    for (parts, (start, end)) in items.into_iter() {
+        let mut separate = prev_end != Some(start);
+        prev_end = Some(end);
+        for part in parts.into_iter() {
+            if let Part::Combinator(comb) = part {
+                if pending.is_some() {
+                    return Err("combinator must be placed between \
+                                two selectors");
+                }
+                if compounds.len() == 0 {
+                    compounds.push((Combinator::Descendant, Compound {
+                        parent: true, element: None, simple: vec![] }));
+                }
+                pending = Some(comb);
+                continue;
+            }
+            let is_element = match part {
+                Part::Element(_) | Part::Parent => true,
+                _ => false,
+            };
+            if separate || pending.is_some() || compounds.len() == 0 {
+                compounds.push((
+                    pending.take().unwrap_or(Combinator::Descendant),
+                    Compound {
+                        parent: false,
+                        element: None,
+                        simple: vec![],
+                    }));
+                separate = false;
+            } else if is_element {
+                return Err("element name or `&` must be at the start of \
+                            the selector or after a combinator");
+            }
+            let cur = &mut compounds.last_mut().unwrap().1;
+            match part {
+                Part::Parent => cur.parent = true,
+                Part::Element(name) => cur.element = Some(name),
+                Part::Simple(simple) => cur.simple.push(simple),
+                Part::Combinator(_) => unreachable!(),
+            }
+        }
+    }
+    if pending.is_some() {
+        return Err("selector can't end with a combinator");
+    }
+    let (_, first) = compounds.remove(0);
+    Ok(Selector {
+        first: first,
+        rest: compounds,
+    })
+}
+
 fn selector<'a>(input: State<'a>) -> Result<'a, Selector>
 {
-    optional(lift(Tok::CssWord).map(ParseToken::into_string))
-        .and(many::<Vec<_>, _>(
-            lift(Tok::Dot).with(lift(Tok::CssWord)
-                .map(ParseToken::into_string))))
-        .and(optional(lift(Tok::Colon)
-            .with(lift(Tok::CssWord).map(ParseToken::into_string))))
-    .map(|((element, classes), opt_state)| Selector {
-        element: element,
-        classes: classes,
-        state: opt_state,
+    many1::<Vec<_>, _>(parser(selector_item))
+    .and_then(|items| build_selector(items)
+        .map_err(|e| Error::Message(Info::Borrowed(e))))
+    .parse_state(input)
+}
+
+fn property<'a>(input: State<'a>) -> Result<'a, RuleItem>
+{
+    lift(Tok::CssWord).map(ParseToken::into_string)
+    .skip(lift(Tok::Colon))
+    .and(parser(property_value))
+    .skip(lift(Tok::Newline))
+    .map(|(key, val)| RuleItem::Property(key, val))
+    .parse_state(input)
+}
+
+fn rule_body<'a>(input: State<'a>) -> Result<'a, Vec<RuleItem>>
+{
+    lift(Tok::Indent)
+    .with(many::<Vec<_>, _>(
+        // Nested rule must have a body, which distinguishes `a:hover` rule
+        // from `color: red` property
+        parser(at_rule).map(RuleItem::AtRule)
+        .or(try(parser(nested_rule)).map(RuleItem::Rule))
+        .or(parser(property))))
+    .skip(lift(Tok::Dedent))
+    .parse_state(input)
+}
+
+fn split_items(items: Vec<RuleItem>) -> (Vec<(String, String)>, Vec<Item>) {
+    let mut properties = vec![];
+    let mut nested = vec![];
+    for item in items.into_iter() {
+        match item {
+            RuleItem::Property(key, val) => properties.push((key, val)),
+            RuleItem::Rule(rule) => nested.push(Item::Rule(rule)),
+            RuleItem::AtRule(rule) => nested.push(Item::AtRule(rule)),
+        }
+    }
+    (properties, nested)
+}
+
+fn make_rule(selectors: Vec<Selector>, items: Vec<RuleItem>) -> Rule {
+    let (properties, nested) = split_items(items);
+    Rule {
+        selectors: selectors,
+        properties: properties,
+        nested: nested,
+    }
+}
+
+type Frame = (Vec<String>, Vec<(String, String)>);
+
+// Frames of `@keyframes` are parsed as nested rules with plain words
+fn keyframe(item: RuleItem) -> ::std::result::Result<Frame, &'static str>
+{
+    let rule = match item {
+        RuleItem::Rule(rule) => rule,
+        _ => return Err("@keyframes may only contain frames"),
+    };
+    let mut frames = vec![];
+    for sel in rule.selectors.iter() {
+        match (&sel.first.element, sel.first.simple.len(), sel.rest.len()) {
+            (&Some(ref name), 0, 0) => frames.push(name.clone()),
+            _ => return Err("frame must be `from`, `to` or a percentage"),
+        }
+    }
+    if rule.nested.len() > 0 {
+        return Err("frames of @keyframes can't contain nested rules");
+    }
+    Ok((frames, rule.properties))
+}
+
+fn make_at_rule(name: &str, prelude: String, items: Vec<RuleItem>)
+    -> ::std::result::Result<AtRule, &'static str>
+{
+    match name {
+        "@media" | "@supports" => {
+            if prelude.len() == 0 {
+                return Err("condition expected after @media or @supports");
+            }
+            let (properties, nested) = split_items(items);
+            Ok(AtRule::Conditional(String::from(name), prelude,
+                                   properties, nested))
+        }
+        "@keyframes" => {
+            if prelude.len() == 0 || prelude.contains(' ') {
+                return Err("@keyframes must have a single name");
+            }
+            let mut frames = vec![];
+            for item in items.into_iter() {
+                frames.push(try!(keyframe(item)));
+            }
+            Ok(AtRule::Keyframes(prelude, frames))
+        }
+        "@font-face" => {
+            if prelude.len() > 0 {
+                return Err("@font-face has no arguments");
+            }
+            let (properties, nested) = split_items(items);
+            if nested.len() > 0 {
+                return Err("@font-face may only contain properties");
+            }
+            Ok(AtRule::FontFace(properties))
+        }
+        _ => Err("unsupported at-rule, expected one of @media, @supports, \
+                  @keyframes, @font-face"),
+    }
+}
+
+fn at_rule<'a>(input: State<'a>) -> Result<'a, AtRule>
+{
+    lift(Tok::CssAt)
+    .and(parser(raw_tokens))
+    .skip(lift(Tok::Newline))
+    .and(optional(parser(rule_body)))
+    .and_then(|((name, prelude), items)| {
+        make_at_rule(name.1, tokens_text(&prelude), items.unwrap_or(vec![]))
+        .map_err(|e| Error::Message(Info::Borrowed(e)))
     })
     .parse_state(input)
 }
 
+fn nested_rule<'a>(input: State<'a>) -> Result<'a, Rule>
+{
+    sep_by1::<Vec<_>, _, _>(parser(selector), lift(Tok::Comma))
+    .skip(lift(Tok::Newline))
+    .and(parser(rule_body))
+    .map(|(selectors, items)| make_rule(selectors, items))
+    .parse_state(input)
+}
+
 fn rule<'a>(input: State<'a>) -> Result<'a, Rule>
 {
     sep_by::<Vec<_>, _, _>(
             parser(selector),
             lift(Tok::Comma),
         ).skip(lift(Tok::Newline))
-    .and(optional(
-        lift(Tok::Indent)
-        .with(many::<Vec<_>, _>(
-            lift(Tok::CssWord).map(ParseToken::into_string)
-            .skip(lift(Tok::Colon))
-            .and(optional(parser(property_value)))
-            .skip(lift(Tok::Newline))
-            )
-        ).skip(lift(Tok::Dedent))
-    ))
-    .map(|(selectors, properties)| {
-        Rule {
-            selectors: selectors,
-            properties: properties.unwrap_or(vec!())
-                .into_iter()
-                .map(|(key, val)| (key, val.unwrap_or(String::new())))
-                .collect(),
+    .and(optional(parser(rule_body)))
+    .and_then(|(selectors, items)| {
+        if selectors.iter().any(|s| s.has_parent()) {
+            return Err(Error::Message(Info::Borrowed(
+                "`&` and leading combinators are only allowed \
+                 in nested rules")));
         }
+        Ok(make_rule(selectors, items.unwrap_or(vec!())))
     })
     .parse_state(input)
 }
@@ -106,20 +571,29 @@ fn rule<'a>(input: State<'a>) -> Result<'a, Rule>
 
 pub fn block<'a>(input: State<'a>) -> Result<'a, Block>
 {
-    optional(lift(Tok::OpenParen)
+    optional(lift(Tok::CssWord).and_then(|tok| if tok.1 == "global" {
+            Ok(())
+        } else {
+            Err(Error::Message(Info::Borrowed(
+                "expected `global`, parameters or colon after `css`")))
+        }))
+    .and(optional(lift(Tok::OpenParen)
         .with(sep_by::<Vec<_>, _, _>(parser(param), lift(Tok::Comma)))
-        .skip(lift(Tok::CloseParen)))
+        .skip(lift(Tok::CloseParen))))
         .skip(lift(Tok::Colon))
         .skip(lift(Tok::Newline))
         .and(optional(
             lift(Tok::Indent)
-            .with(many::<Vec<_>, _>(parser(rule)))
+            .with(many::<Vec<_>, _>(
+                parser(at_rule).map(Item::AtRule)
+                .or(parser(rule).map(Item::Rule))))
             .skip(lift(Tok::Dedent))
         ))
-        .map(|(opt_params, opt_rules)| {
+        .map(|((global, opt_params), opt_rules)| {
             Block::Css(
                 opt_params.unwrap_or(vec!()),
                 opt_rules.unwrap_or(vec!()),
+                global.is_some(),
             )
         })
         .parse_state(input)
@@ -1,6 +1,9 @@
-use combine::{parser, Parser};
-use combine::combinator::{optional, ParserExt, sep_by, many, many1};
+use std::fmt;
+
+use combine::{parser, Parser, try, satisfy};
+use combine::combinator::{optional, ParserExt, sep_by, sep_by1, many, many1};
 use combine::combinator::{chainl1, between, choice, sep_end_by};
+use combine::primitives::{Error, Info};
 
 use util::join;
 
@@ -17,7 +20,20 @@ type ChainFun = fn(Expression, Expression) -> Expression;
 #[derive(Debug, Clone)]
 pub struct Param {
     pub name: String,
-    pub default_value: Option<String>,
+    pub typ: Option<Type>,
+    pub default_value: Option<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Str,
+    Int,
+    Float,
+    Bool,
+    List,
+    Dict,
+    // Constructor imported by the module, checked with `instanceof`
+    Class(String),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -47,7 +63,7 @@ pub enum Expression {
     Sub(Box<Expression>, Box<Expression>),
     Comparison(Comparator, Box<Expression>, Box<Expression>),
     Item(Box<Expression>, Box<Expression>),
-    Call(Box<Expression>, Vec<Expression>),
+    Call(Box<Expression>, Vec<Expression>, Vec<(String, Expression)>),
     Dict(Vec<(String, Expression)>),
     List(Vec<Expression>),
 }
@@ -72,6 +88,12 @@ pub enum Fmt {
     Str(Expression),  // TODO(tailhook) need formatting or padding?
 }
 
+#[derive(Debug, Clone)]
+pub enum Target {
+    Name(String),
+    Destructure(Vec<String>),
+}
+
 #[derive(Debug, Clone)]
 pub enum Statement {
     Element {
@@ -86,17 +108,108 @@ pub enum Statement {
     Let(String, Expression),
     Link(Vec<Link>),
     Condition(Vec<(Expression, Vec<Statement>)>, Option<Vec<Statement>>),
-    ForOf(String, Expression, Option<Expression>, Vec<Statement>),
+    Match(Expression, Vec<(Vec<Expression>, Vec<Statement>)>,
+          Option<Vec<Statement>>),
+    Try(Vec<Statement>, Option<String>, Vec<Statement>),
+    ForOf {
+        index: Option<String>,
+        target: Target,
+        iterable: Expression,
+        key: Option<Expression>,
+        body: Vec<Statement>,
+        fallback: Option<Vec<Statement>>,
+    },
+    ForIn {
+        name: String,
+        value: Option<Target>,
+        object: Expression,
+        key: Option<Expression>,
+        body: Vec<Statement>,
+        fallback: Option<Vec<Statement>>,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+        keywords: Vec<(String, Expression)>,
+        links: Vec<Link>,
+    },
+}
+
+
+impl Expression {
+    pub fn is_constant(&self) -> bool {
+        use self::Expression::*;
+        match *self {
+            Str(_) | Num(_) => true,
+            Name(ref name) => match &name[..] {
+                "true" | "false" | "null" => true,
+                _ => false,
+            },
+            Format(ref items) => items.iter().all(|x| match x {
+                &Fmt::Raw(_) => true,
+                _ => false,
+            }),
+            Not(ref a) => a.is_constant(),
+            And(ref a, ref b) | Or(ref a, ref b)
+            | Mul(ref a, ref b) | Div(ref a, ref b)
+            | Add(ref a, ref b) | Sub(ref a, ref b)
+            | Comparison(_, ref a, ref b)
+            => a.is_constant() && b.is_constant(),
+            Dict(ref items) => items.iter().all(|&(_, ref x)| x.is_constant()),
+            List(ref items) => items.iter().all(|x| x.is_constant()),
+            New(_) | Attr(_, _) | Item(_, _) | Call(_, _, _) => false,
+        }
+    }
 }
 
+impl Type {
+    fn from_name(name: String) -> Type {
+        match &name[..] {
+            "str" => Type::Str,
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "bool" => Type::Bool,
+            "list" => Type::List,
+            "dict" => Type::Dict,
+            _ => Type::Class(name),
+        }
+    }
+    // Int is accepted where float is expected
+    pub fn accepts(&self, other: &Type) -> bool {
+        self == other || (*self == Type::Float && *other == Type::Int)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Str => write!(f, "str"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::List => write!(f, "list"),
+            Type::Dict => write!(f, "dict"),
+            Type::Class(ref name) => write!(f, "{}", name),
+        }
+    }
+}
 
 fn param<'x>(input: State<'x>) -> Result<'x, Param>
 {
-    lift(Tok::Ident).and(optional(lift(Tok::Equals)
-        .with(lift(Tok::String))))  // TODO(tailhook) more expressions
-    .map(|(Token(_, name, _), opt)| Param {
+    lift(Tok::Ident)
+    .and(optional(lift(Tok::Colon).with(lift(Tok::Ident)
+        .map(ParseToken::into_string).map(Type::from_name))))
+    .and(optional(lift(Tok::Equals)
+        .with(parser(expression).and_then(|x| if x.is_constant() {
+                Ok(x)
+            } else {
+                Err(Error::Message(Info::Borrowed(
+                    "default value must be a constant expression")))
+            }))))
+    .map(|((Token(_, name, _), typ), opt)| Param {
         name: String::from(name),
-        default_value: opt.map(ParseToken::unescape)
+        typ: typ,
+        default_value: opt,
     })
     .parse_state(input)
 }
@@ -227,28 +340,68 @@ fn literal<'a>(input: State<'a>) -> Result<'a, Statement>
     .parse_state(input)
 }
 
+fn arguments<'a>(input: State<'a>)
+    -> Result<'a, (Vec<Expression>, Vec<(String, Expression)>)>
+{
+    sep_end_by::<Vec<_>, _, _>(
+        optional(try(lift(Tok::Ident).map(ParseToken::into_string)
+                     .skip(lift(Tok::Equals))))
+        .and(parser(expression)),
+        lift(Tok::Comma))
+    .and_then(|items| {
+        let mut args = vec!();
+        let mut kwargs = vec!();
+        for (name, value) in items.into_iter() {
+            match name {
+                Some(name) => kwargs.push((name, value)),
+                None if kwargs.len() > 0 => {
+                    return Err(Error::Message(Info::Borrowed(
+                        "positional argument follows keyword argument")));
+                }
+                None => args.push(value),
+            }
+        }
+        Ok((args, kwargs))
+    })
+    .parse_state(input)
+}
+
+// Keywords are valid attribute names, e.g. `text.match(re)` or `p.try`
+fn attr_name<'a>(input: State<'a>) -> Result<'a, String>
+{
+    satisfy(|tok: Token<'a>| match tok.0 {
+        Tok::Ident | Tok::Css | Tok::Html | Tok::Const | Tok::Import
+        | Tok::From | Tok::If | Tok::Elif | Tok::For | Tok::In | Tok::Of
+        | Tok::Key | Tok::As | Tok::Else | Tok::Match | Tok::Case | Tok::Try
+        | Tok::Except | Tok::Events | Tok::Link | Tok::Store | Tok::Let
+        | Tok::New | Tok::And | Tok::Or | Tok::Not => true,
+        _ => false,
+    })
+    .map(ParseToken::into_string)
+    .parse_state(input)
+}
+
 fn call<'a>(input: State<'a>) -> Result<'a, Expression>
 {
     enum Sub {
         GetAttr(String),
         GetItem(Expression),
-        Call(Vec<Expression>),
+        Call((Vec<Expression>, Vec<(String, Expression)>)),
     }
     parser(atom)
     .and(many::<Vec<_>,_>(
-        lift(Tok::Dot).with(lift(Tok::Ident))
-            .map(ParseToken::into_string).map(Sub::GetAttr)
+        lift(Tok::Dot).with(parser(attr_name)).map(Sub::GetAttr)
         .or(between(lift(Tok::OpenBracket), lift(Tok::CloseBracket),
                     parser(expression)).map(Sub::GetItem))
         .or(between(lift(Tok::OpenParen), lift(Tok::CloseParen),
-                    sep_end_by::<Vec<_>, _, _>(parser(expression),
-                                           lift(Tok::Comma))
-                    .map(Sub::Call)))))
+                    parser(arguments))
+                    .map(Sub::Call))))
     .map(|(expr, suffixes)|
         suffixes.into_iter().fold(expr, |expr, sub| match sub {
             Sub::GetAttr(x) => Expression::Attr(Box::new(expr), x),
             Sub::GetItem(x) => Expression::Item(Box::new(expr), Box::new(x)),
-            Sub::Call(x) => Expression::Call(Box::new(expr), x),
+            Sub::Call((args, kwargs))
+            => Expression::Call(Box::new(expr), args, kwargs),
         }))
     .parse_state(input)
 }
@@ -435,6 +588,28 @@ fn link<'a>(input: State<'a>) -> Result<'a, Statement>
     .parse_state(input)
 }
 
+fn call_statement<'a>(input: State<'a>) -> Result<'a, Statement>
+{
+    try(lift(Tok::Ident).skip(lift(Tok::OpenParen)))
+    .and(parser(arguments))
+    .skip(lift(Tok::CloseParen))
+    .skip(lift(Tok::Newline))
+    .and(optional(
+        lift(Tok::Indent)
+        .with(many1::<Vec<_>, _>(parser(link)))
+        .skip(lift(Tok::Dedent))))
+    .map(|((name, (arguments, keywords)), opt_links)| Statement::Call {
+        name: name.into_string(),
+        arguments: arguments,
+        keywords: keywords,
+        links: opt_links.map(|x| x.into_iter().flat_map(|stmt| match stmt {
+            Statement::Link(links) => links,
+            _ => unreachable!(),
+        }).collect()).unwrap_or(vec!()),
+    })
+    .parse_state(input)
+}
+
 fn condition<'a>(input: State<'a>) -> Result<'a, Statement>
 {
     lift(Tok::If)
@@ -467,11 +642,83 @@ fn condition<'a>(input: State<'a>) -> Result<'a, Statement>
     .parse_state(input)
 }
 
+fn match_case<'a>(input: State<'a>) -> Result<'a, Statement>
+{
+    lift(Tok::Match)
+    .with(parser(expression))
+    .skip(lift(Tok::Colon))
+    .skip(lift(Tok::Newline))
+    .skip(lift(Tok::Indent))
+    .and(many1::<Vec<_>,_>(
+        lift(Tok::Case)
+        .with(sep_by1::<Vec<_>, _, _>(parser(expression), lift(Tok::Comma)))
+        .skip(lift(Tok::Colon))
+        .skip(lift(Tok::Newline))
+        .and(parser(chunk))
+        ))
+    .and(optional(lift(Tok::Else)
+        .skip(lift(Tok::Colon))
+        .skip(lift(Tok::Newline))
+        .with(parser(chunk))
+        ))
+    .skip(lift(Tok::Dedent))
+    .map(|((value, cases), opt_else)| Statement::Match(
+        value,
+        cases.into_iter()
+             .map(|(values, opt_body)| (values, opt_body.unwrap_or(vec!())))
+             .collect(),
+        opt_else.map(|x| x.unwrap_or(vec!()))))
+    .parse_state(input)
+}
+
+fn try_except<'a>(input: State<'a>) -> Result<'a, Statement>
+{
+    lift(Tok::Try)
+    .skip(lift(Tok::Colon))
+    .skip(lift(Tok::Newline))
+    .with(parser(chunk))
+    .skip(lift(Tok::Except))
+    .and(optional(lift(Tok::Ident).map(ParseToken::into_string)))
+    .skip(lift(Tok::Colon))
+    .skip(lift(Tok::Newline))
+    .and(parser(chunk))
+    .map(|((body, name), fallback)| Statement::Try(
+        body.unwrap_or(vec!()), name, fallback.unwrap_or(vec!())))
+    .parse_state(input)
+}
+
+fn loop_target<'a>(input: State<'a>) -> Result<'a, Target>
+{
+    lift(Tok::Ident).map(ParseToken::into_string).map(Target::Name)
+    .or(between(lift(Tok::OpenBrace), lift(Tok::CloseBrace),
+        sep_end_by::<Vec<_>, _, _>(
+            lift(Tok::Ident).map(ParseToken::into_string),
+            lift(Tok::Comma)))
+        .map(Target::Destructure))
+    .parse_state(input)
+}
+
+fn loop_head<'a>(input: State<'a>)
+    -> Result<'a, (Option<String>, Target)>
+{
+    parser(loop_target)
+    .and(optional(lift(Tok::Comma).with(parser(loop_target))))
+    .and_then(|(first, second)| match (first, second) {
+        (target, None) => Ok((None, target)),
+        (Target::Name(index), Some(target)) => Ok((Some(index), target)),
+        (Target::Destructure(_), Some(_)) => {
+            Err(Error::Message(Info::Borrowed(
+                "loop index or object key must be a plain name")))
+        }
+    })
+    .parse_state(input)
+}
+
 fn iteration<'a>(input: State<'a>) -> Result<'a, Statement>
 {
     lift(Tok::For)
-    .with(lift(Tok::Ident))
-    .skip(lift(Tok::Of))
+    .with(parser(loop_head))
+    .and(lift(Tok::Of).or(lift(Tok::In)))
     .and(parser(expression))
     .and(optional(
         lift(Tok::Key)
@@ -479,9 +726,43 @@ fn iteration<'a>(input: State<'a>) -> Result<'a, Statement>
     .skip(lift(Tok::Colon))
     .skip(lift(Tok::Newline))
     .and(parser(chunk))
-    .map(|(((name, array), opt_key), opt_body)| Statement::ForOf(
-        name.into_string(), array, opt_key,
-        opt_body.unwrap_or(vec!())))
+    .and(optional(lift(Tok::Else)
+        .skip(lift(Tok::Colon))
+        .skip(lift(Tok::Newline))
+        .with(parser(chunk))
+        ))
+    .and_then(|((((((index, target), Token(tok, _, _)), collection), opt_key),
+                 opt_body), opt_else)|
+        match (tok, index, target) {
+            (Tok::Of, index, target) => Ok(Statement::ForOf {
+                index: index,
+                target: target,
+                iterable: collection,
+                key: opt_key,
+                body: opt_body.unwrap_or(vec!()),
+                fallback: opt_else.map(|x| x.unwrap_or(vec!())),
+            }),
+            (_, None, Target::Name(name)) => Ok(Statement::ForIn {
+                name: name,
+                value: None,
+                object: collection,
+                key: opt_key,
+                body: opt_body.unwrap_or(vec!()),
+                fallback: opt_else.map(|x| x.unwrap_or(vec!())),
+            }),
+            (_, Some(name), value) => Ok(Statement::ForIn {
+                name: name,
+                value: Some(value),
+                object: collection,
+                key: opt_key,
+                body: opt_body.unwrap_or(vec!()),
+                fallback: opt_else.map(|x| x.unwrap_or(vec!())),
+            }),
+            (_, None, Target::Destructure(_)) => {
+                Err(Error::Message(Info::Borrowed(
+                    "object key must be a plain name")))
+            }
+        })
     .parse_state(input)
 }
 
@@ -496,12 +777,15 @@ fn output<'a>(input: State<'a>) -> Result<'a, Statement>
 
 fn statement<'a>(input: State<'a>) -> Result<'a, Statement>
 {
-    parser(element)
+    parser(call_statement)
+    .or(parser(element))
     .or(parser(literal))
     .or(parser(store))
     .or(parser(let_var))
     .or(parser(link))
     .or(parser(condition))
+    .or(parser(match_case))
+    .or(parser(try_except))
     .or(parser(iteration))
     .or(parser(output))
     .parse_state(input)
@@ -4,11 +4,12 @@ extern crate marafet_util as util;
 
 use combine::combinator::{many, ParserExt};
 use combine::{Parser, ParseResult, parser, optional, sep_end_by};
+use combine::primitives::Stream as StreamTrait;
 
-use self::token::{ParseToken, lift};
-use self::token::TokenType::{Css, Html, Eof};
+use self::token::{Token, ParseToken, lift};
+use self::token::TokenType::{Css, Html, Const, Eof, Not, CssWord};
 use self::token::TokenType::{Import, From, Comma, Newline};
-use self::token::TokenType::{OpenBrace, CloseBrace, Ident, As};
+use self::token::TokenType::{OpenBrace, CloseBrace, Ident, As, Equals};
 use self::token::TokenType::String as StrTok;
 use self::tokenizer::Tokenizer;
 
@@ -24,7 +25,8 @@ pub type Result<'a, T> = combine::primitives::ParseResult<T, Stream<'a>>;
 
 #[derive(Debug, Clone)]
 pub enum Block {
-    Css(Vec<css::Param>, Vec<css::Rule>),
+    // Last field is true for `css global:` blocks, which aren't scoped
+    Css(Vec<css::Param>, Vec<css::Item>, bool),
     Html {
         name: String,
         params: Vec<html::Param>,
@@ -33,6 +35,7 @@ pub enum Block {
     },
     ImportModule(String, String),
     ImportVars(Vec<(String, Option<String>)>, String),
+    Const(String, html::Expression),
 }
 
 #[derive(Debug, Clone)]
@@ -66,12 +69,22 @@ fn import<'x>(input: State<'x>) -> Result<'x, Block>
     .parse_state(input)
 }
 
+fn constant<'x>(input: State<'x>) -> Result<'x, Block>
+{
+    lift(Ident).map(ParseToken::into_string)
+        .skip(lift(Equals)).and(parser(html::expression))
+        .skip(lift(Newline))
+        .map(|(name, value)| Block::Const(name, value))
+    .parse_state(input)
+}
+
 fn body<'x>(input: State<'x>) -> Result<'x, Ast>
 {
     let css = lift(Css).with(parser(css::block));
     let html = lift(Html).with(parser(html::block));
     let import = lift(Import).with(parser(import));
-    let block = css.or(html).or(import);
+    let constant = lift(Const).with(parser(constant));
+    let block = css.or(html).or(import).or(constant);
     let blocks = many::<Vec<_>, _>(block).skip(lift(Eof));
     return blocks.map(|blocks| Ast {
         blocks: blocks,
@@ -94,3 +107,24 @@ pub fn parse_html_expr(text: &str)
     .map(|(ast, _)| ast)
 }
 
+// Lines and descriptions of obsolete syntax constructs in the source
+pub fn deprecated_syntax(text: &str) -> Vec<(i32, &'static str)> {
+    let mut res = vec![];
+    let mut tokens = Tokenizer::new(text);
+    loop {
+        tokens = match tokens.uncons() {
+            Ok((Token(Eof, _, _), _)) | Err(_) => break,
+            Ok((Token(Not, "!", pos), next)) => {
+                // `!important` in css blocks is fine
+                match next.clone().uncons() {
+                    Ok((Token(CssWord, "important", _), _)) => {}
+                    _ => res.push((pos.line,
+                                   "`!` is deprecated, use `not` instead")),
+                }
+                next
+            }
+            Ok((_, next)) => next,
+        };
+    }
+    return res;
+}
@@ -11,6 +11,7 @@ use super::{Stream, State, Result};
 pub enum TokenType {
     Css,
     Html,
+    Const,
     Import,
     From,
     If,
@@ -21,6 +22,10 @@ pub enum TokenType {
     Key,
     As,
     Else,
+    Match,
+    Case,
+    Try,
+    Except,
     Events,
     Link,
     Store,
@@ -54,6 +59,10 @@ pub enum TokenType {
     CloseBrace,     // }
     Ident,
     CssWord,
+    CssVar,
+    CssHash,        // #word (in css blocks only)
+    CssAt,          // @word (in css blocks only)
+    CssSymbol,      // ~ ^ | & $ (in css blocks only)
     Number,
     String,
     Newline,
@@ -147,6 +156,10 @@ impl TokenType {
             TokenType::CloseBrace => Info::Borrowed("}"),
             TokenType::Ident => Info::Borrowed("identifier"),
             TokenType::CssWord => Info::Borrowed("css word or identifier"),
+            TokenType::CssVar => Info::Borrowed("css variable"),
+            TokenType::CssHash => Info::Borrowed("#id or #color"),
+            TokenType::CssAt => Info::Borrowed("@media, @keyframes, ..."),
+            TokenType::CssSymbol => Info::Borrowed("css symbol"),
             TokenType::Number => Info::Borrowed("number"),
             TokenType::String => Info::Borrowed("quoted string"),
             TokenType::Newline => Info::Borrowed("new line"),
@@ -170,6 +183,7 @@ impl TokenType {
             TokenType::ArrowRight => Info::Borrowed("arrow right"),
             TokenType::Eof => Info::Borrowed("end of file"),
             TokenType::Import => Info::Borrowed("import"),
+            TokenType::Const => Info::Borrowed("const NAME = VALUE"),
             TokenType::From => Info::Borrowed("from"),
             TokenType::If => Info::Borrowed("if"),
             TokenType::Elif => Info::Borrowed("elif"),
@@ -179,6 +193,10 @@ impl TokenType {
             TokenType::Key => Info::Borrowed("key"),
             TokenType::As => Info::Borrowed("as"),
             TokenType::Else => Info::Borrowed("else"),
+            TokenType::Match => Info::Borrowed("match"),
+            TokenType::Case => Info::Borrowed("case"),
+            TokenType::Try => Info::Borrowed("try"),
+            TokenType::Except => Info::Borrowed("except"),
             TokenType::Events => Info::Borrowed("events"),
             TokenType::Link => Info::Borrowed("link"),
             TokenType::Store => Info::Borrowed("store"),
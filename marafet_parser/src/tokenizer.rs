@@ -82,6 +82,13 @@ pub struct Tokenizer<'a> {
     mode: Mode,
 }
 
+fn is_css_word_char(ch: char) -> bool {
+    match ch {
+        'a'...'z'|'A'...'Z'|'0'...'9'|'_'|'-' => true,
+        _ => false,
+    }
+}
+
 impl<'a> Tokenizer<'a> {
 
     pub fn new(val: &'a str) -> Tokenizer<'a> {
@@ -101,6 +108,20 @@ impl<'a> Tokenizer<'a> {
         };
     }
 
+    // In css blocks `#` followed by a word is an id selector or a color,
+    // rather than a comment. Iterator must point to the `#` itself
+    fn is_css_hash(&self, iter: &CodeIter<'a>) -> bool {
+        if self.mode != Mode::Css {
+            return false;
+        }
+        let mut iter = iter.clone();
+        iter.next();
+        match iter.peek() {
+            Some((ch, _, _, _)) => is_css_word_char(ch),
+            None => false,
+        }
+    }
+
     fn next(&mut self)
         -> Result<(TokenType, &'a str, SourcePosition),
                   Error<Token<'a>, Token<'a>>>
@@ -117,7 +138,8 @@ impl<'a> Tokenizer<'a> {
                         niter.next();
                         match niter.peek() {
                             Some((' ', _, _, _)) => continue,
-                            Some(('#', _, _, _)) => {
+                            Some(('#', _, _, _)) if !self.is_css_hash(&niter)
+                            => {
                                 self.iter = niter;
                                 loop {
                                     match self.iter.next() {
@@ -226,6 +248,73 @@ impl<'a> Tokenizer<'a> {
                                     Info::Token(Token(tok.0, tok.1, tok.2))));
                             }
                         }
+                        '#'|'@' if self.mode == Mode::Css &&
+                            self.iter.peek().map(|(ch, _, _, _)|
+                                is_css_word_char(ch)).unwrap_or(false)
+                        => {
+                            let mut offset = self.data.len();
+                            loop {
+                                match self.iter.peek() {
+                                    Some((x, off, _, _)) => {
+                                        if !is_css_word_char(x) {
+                                            offset = off;
+                                            break;
+                                        }
+                                    }
+                                    None => break,
+                                }
+                                self.iter.next();
+                            }
+                            let typ = if ch == '#' {
+                                TokenType::CssHash
+                            } else {
+                                TokenType::CssAt
+                            };
+                            return Ok((typ, &self.data[off..offset], pos));
+                        }
+                        '@' if self.mode == Mode::Css => {
+                            return Err(Error::Message(Info::Owned(
+                                format!("at-rule name expected after \
+                                         `@` at {}", pos))));
+                        }
+                        '~'|'^'|'|'|'&' if self.mode == Mode::Css => {
+                            return Ok((TokenType::CssSymbol,
+                                &self.data[off..off+1], pos));
+                        }
+                        '$' if self.mode == Mode::Css &&
+                            self.iter.peek().map(|(ch, _, _, _)| ch == '=')
+                                .unwrap_or(false)
+                        => {
+                            return Ok((TokenType::CssSymbol,
+                                &self.data[off..off+1], pos));
+                        }
+                        '$' if self.mode == Mode::Css => {
+                            let mut offset = self.data.len();
+                            loop {
+                                match self.iter.peek() {
+                                    Some((x, off, _, _)) => {
+                                        match x {
+                                            'a'...'z'|'A'...'Z'
+                                            |'0'...'9'|'_'|'-'
+                                            => {}
+                                            _ => {
+                                                offset = off;
+                                                break;
+                                            }
+                                        }
+                                    }
+                                    None => break,
+                                }
+                                self.iter.next();
+                            }
+                            if offset == off + 1 {
+                                return Err(Error::Message(Info::Owned(
+                                    format!("variable name expected after \
+                                             `$` at {}", pos))));
+                            }
+                            return Ok((TokenType::CssVar,
+                                &self.data[off..offset], pos));
+                        }
                         'a'...'z'|'A'...'Z'|'_'|'-'|'0'...'9'
                         if self.mode == Mode::Css => {
                             let mut offset = self.data.len();
@@ -234,7 +323,7 @@ impl<'a> Tokenizer<'a> {
                                     Some((x, off, _, _)) => {
                                         match x {
                                             'a'...'z'|'A'...'Z'
-                                            |'0'...'9'|'_'|'-'|'.'
+                                            |'0'...'9'|'_'|'-'|'.'|'%'
                                             => {}
                                             _ => {
                                                 offset = off;
@@ -252,6 +341,13 @@ impl<'a> Tokenizer<'a> {
                                     self.mode = Mode::Normal;
                                     return Ok((TokenType::Html, value, pos));
                                 }
+                                "const" if column == 1 => {
+                                    self.mode = Mode::Normal;
+                                    return Ok((TokenType::Const, value, pos));
+                                }
+                                "css" if column == 1 => {
+                                    return Ok((TokenType::Css, value, pos));
+                                }
                                 _ => {
                                     return Ok((TokenType::CssWord,
                                                  value, pos));
@@ -284,7 +380,7 @@ impl<'a> Tokenizer<'a> {
                                             len = 2;
                                             TokenType::NotEq
                                         }
-                                        _ => TokenType::Not, //TODO remove?
+                                        _ => TokenType::Not, // deprecated, use `not`
                                     }
                                 }
                                 '>' => {
@@ -389,6 +485,7 @@ impl<'a> Tokenizer<'a> {
                                     TokenType::Css
                                 }
                                 "html" => TokenType::Html,
+                                "const" => TokenType::Const,
                                 "import" => TokenType::Import,
                                 "from" => TokenType::From,
                                 "if" => TokenType::If,
@@ -399,6 +496,10 @@ impl<'a> Tokenizer<'a> {
                                 "as" => TokenType::As,
                                 "key" => TokenType::Key,
                                 "else" => TokenType::Else,
+                                "match" => TokenType::Match,
+                                "case" => TokenType::Case,
+                                "try" => TokenType::Try,
+                                "except" => TokenType::Except,
                                 "events" => TokenType::Events,
                                 "store" => TokenType::Store,
                                 "let" => TokenType::Let,
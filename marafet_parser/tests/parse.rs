@@ -0,0 +1,114 @@
+extern crate marafet_parser as parser;
+
+
+fn parse_error(text: &str) -> String {
+    match parser::parse_string(text) {
+        Ok(_) => panic!("Parsed successfully: {:?}", text),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn constant_defaults() {
+    parser::parse_string(
+        "html foo(a=1, b=\"x\", c=[1, 2], d={x: 0 - 1}, e=null, f=1 + 2):\n\
+         \x20 \"{a}\"\n").unwrap();
+}
+
+#[test]
+fn non_constant_default() {
+    let err = parse_error("html foo(a=b):\n  \"{a}\"\n");
+    assert!(err.contains("must be a constant"), "{}", err);
+    let err = parse_error("html foo(a=f(1)):\n  \"{a}\"\n");
+    assert!(err.contains("must be a constant"), "{}", err);
+}
+
+fn css_defaults(text: &str) -> Vec<Option<String>> {
+    let ast = parser::parse_string(text).unwrap();
+    match ast.blocks[0] {
+        parser::Block::Css(ref params, _, _) => {
+            params.iter().map(|p| p.default_value.clone()).collect()
+        }
+        _ => panic!("Not a css block"),
+    }
+}
+
+#[test]
+fn css_param_defaults() {
+    assert_eq!(css_defaults(
+        "css(border=1px solid red, shadow=0 0 2px rgba(0, 0, 0, 0.5), \
+             font=\"Helvetica\", size, color=#fff !important):\n"),
+        vec![Some(String::from("1px solid red")),
+             Some(String::from("0 0 2px rgba(0, 0, 0, 0.5)")),
+             Some(String::from("Helvetica")),
+             None,
+             Some(String::from("#fff !important"))]);
+    let err = parse_error("css(color=red ! blue):\n");
+    assert!(err.contains("`!` is only allowed"), "{}", err);
+}
+
+#[test]
+fn css_hash_and_comments() {
+    // In css blocks `#` followed by a word is an id or a color, comments
+    // need a space after `#`
+    let ast = parser::parse_string(
+        "css:\n  # note\n  #note\n    color: #fff # white\n").unwrap();
+    match ast.blocks[0] {
+        parser::Block::Css(_, ref items, _) => {
+            assert_eq!(items.len(), 1);
+            match items[0] {
+                parser::css::Item::Rule(ref rule) => {
+                    assert_eq!(format!("{:?}", rule.selectors[0].first.simple),
+                               "[Id(\"note\")]");
+                    assert_eq!(rule.properties, vec![
+                        (String::from("color"), String::from("#fff"))]);
+                }
+                _ => panic!("Not a rule"),
+            }
+        }
+        _ => panic!("Not a css block"),
+    }
+}
+
+#[test]
+fn loop_index() {
+    parser::parse_string(
+        "html foo(items):\n  for i, {a, b} of items key a:\n    \"{i}\"\n")
+        .unwrap();
+    let err = parse_error(
+        "html foo(items):\n  for {a}, b of items:\n    \"{b}\"\n");
+    assert!(err.contains("must be a plain name"), "{}", err);
+}
+
+#[test]
+fn for_in_destructured_key() {
+    let err = parse_error(
+        "html foo(obj):\n  for {a, b} in obj:\n    \"{a}\"\n");
+    assert!(err.contains("object key must be a plain name"), "{}", err);
+}
+
+#[test]
+fn match_without_cases() {
+    let err = parse_error(
+        "html foo(x):\n  match x:\n    else:\n      \"{x}\"\n");
+    assert!(err.contains("case"), "{}", err);
+}
+
+#[test]
+fn css_element_after_class() {
+    let err = parse_error("css:\n  .b&\n    color: red\n");
+    assert!(err.contains("must be at the start"), "{}", err);
+}
+
+#[test]
+fn keyword_attributes() {
+    parser::parse_string(
+        "html foo(text, re, p):\n  \"{text.match(re)} {p.try.key}\"\n")
+        .unwrap();
+}
+
+#[test]
+fn destructured_loop_without_key() {
+    parser::parse_string(
+        "html foo(items):\n  for {a, b} of items:\n    \"{a}\"\n").unwrap();
+}
@@ -1,3 +1,5 @@
 mod string;
+mod scoping;
 
 pub use string::join;
+pub use scoping::{Scoping, selector_classes, short_name};
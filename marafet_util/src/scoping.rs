@@ -0,0 +1,108 @@
+// How class names of the block are made unique
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scoping {
+    // `.x` becomes `.block.x`
+    BlockClass,
+    // `.x` becomes `.block.block-x`, block name is usually derived from
+    // the path of the file
+    Prefix,
+    // `.x` becomes something like `.m1x2k3.m9zq0s`, mapping from original
+    // names is exported for the application code
+    Hash,
+}
+
+// FNV-1a, so names are stable across compiler versions and platforms
+fn hash(data: &str) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    for byte in data.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+// Short name which is valid as a class name or an element id
+pub fn short_name(data: &str) -> String {
+    let mut value = hash(data);
+    let mut digits = vec![];
+    while value > 0 {
+        digits.push(b"0123456789abcdefghijklmnopqrstuvwxyz"
+            [(value % 36) as usize] as char);
+        value /= 36;
+    }
+    digits.push('m');
+    digits.into_iter().rev().collect()
+}
+
+impl Scoping {
+    pub fn from_name(name: &str) -> Option<Scoping> {
+        match name {
+            "block" => Some(Scoping::BlockClass),
+            "prefix" => Some(Scoping::Prefix),
+            "hash" => Some(Scoping::Hash),
+            _ => None,
+        }
+    }
+    // Class which is added to every element of the block
+    pub fn marker(&self, block_name: &str) -> String {
+        match *self {
+            Scoping::BlockClass | Scoping::Prefix => String::from(block_name),
+            Scoping::Hash => short_name(block_name),
+        }
+    }
+    // Name which is unique among all blocks, used for keyframes too
+    pub fn global_name(&self, block_name: &str, name: &str) -> String {
+        match *self {
+            Scoping::BlockClass | Scoping::Prefix
+            => format!("{}-{}", block_name, name),
+            Scoping::Hash => short_name(&format!("{}.{}", block_name, name)),
+        }
+    }
+    pub fn class_name(&self, block_name: &str, name: &str) -> String {
+        match *self {
+            Scoping::BlockClass => String::from(name),
+            Scoping::Prefix | Scoping::Hash
+            => self.global_name(block_name, name),
+        }
+    }
+    // Renames classes in a selector text, like argument of `:not(.x)`
+    pub fn rename_classes(&self, block_name: &str, text: &str) -> String {
+        map_classes(text, |name| self.class_name(block_name, name))
+    }
+}
+
+// Class names in a selector text, like argument of `:not(.x)`
+pub fn selector_classes(text: &str) -> Vec<String> {
+    let mut names = vec![];
+    map_classes(text, |name| {
+        names.push(String::from(name));
+        String::new()
+    });
+    names
+}
+
+// Replaces every class name in a selector text by the result of `f`
+fn map_classes<F>(text: &str, mut f: F) -> String
+    where F: FnMut(&str) -> String
+{
+    let mut res = String::new();
+    let mut class_start = None;
+    let end = Some((text.len(), ' '));
+    for (idx, ch) in text.char_indices().chain(end.into_iter()) {
+        let word_char = match ch {
+            'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => true,
+            _ => false,
+        };
+        if let (Some(start), false) = (class_start, word_char) {
+            res.push_str(&f(&text[start..idx]));
+            class_start = None;
+        }
+        if ch == '.' && idx < text.len() {
+            res.push('.');
+            class_start = Some(idx+1);
+        } else if class_start.is_none() && idx < text.len() {
+            res.push(ch);
+        }
+    }
+    res
+}
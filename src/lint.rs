@@ -0,0 +1,376 @@
+use std::collections::HashSet;
+
+use parser::{Ast, Block};
+use parser::html::{Statement, Target};
+
+
+pub const NAMING_STYLE: &'static str = "naming-style";
+pub const MAX_NESTING_DEPTH: &'static str = "max-nesting-depth";
+pub const DEPRECATED_SYNTAX: &'static str = "deprecated-syntax";
+const RULES: &'static [&'static str] = &[
+    NAMING_STYLE, MAX_NESTING_DEPTH, DEPRECATED_SYNTAX];
+
+
+pub struct Config {
+    disabled: HashSet<String>,
+    max_depth: usize,
+}
+
+// Rules allowed by `# marafet: allow(rule)` comments. Comments at the top
+// level apply to the whole file, comments inside an html block apply to
+// that block only
+struct Allowed {
+    file: HashSet<String>,
+    blocks: Vec<HashSet<String>>,
+    // Index of the html block for every line of the source
+    lines: Vec<Option<usize>>,
+}
+
+struct Linter<'a> {
+    config: &'a Config,
+    allowed: &'a Allowed,
+    block: usize,
+    block_name: &'a String,
+    depth_reported: bool,
+    messages: Vec<String>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            disabled: HashSet::new(),
+            max_depth: 10,
+        }
+    }
+    // Config file consists of `rule = on|off` lines, and a
+    // `max-nesting-depth = N` line
+    pub fn parse(&mut self, text: &str) -> Result<(), String> {
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.len() == 0 || line.starts_with("#") {
+                continue;
+            }
+            let mut pair = line.splitn(2, '=');
+            let name = pair.next().unwrap().trim();
+            let value = try!(pair.next().ok_or_else(|| format!(
+                "Line {}: expected `rule = value`", lineno+1))).trim();
+            if !RULES.contains(&name) {
+                return Err(format!("Line {}: unknown rule {:?}",
+                                   lineno+1, name));
+            }
+            match value {
+                "on" => {
+                    self.disabled.remove(name);
+                }
+                "off" => {
+                    self.disabled.insert(String::from(name));
+                }
+                _ if name == MAX_NESTING_DEPTH => {
+                    self.max_depth = try!(value.parse().map_err(|_| format!(
+                        "Line {}: {} must be a number", lineno+1, name)));
+                }
+                _ => {
+                    return Err(format!("Line {}: {} must be `on` or `off`",
+                                       lineno+1, name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn allow_comment(line: &str) -> Option<Vec<String>> {
+    for (idx, _) in line.match_indices('#') {
+        let comment = line[idx+1..].trim();
+        if comment.starts_with("marafet:") {
+            let comment = comment["marafet:".len()..].trim();
+            if comment.starts_with("allow(") && comment.ends_with(")") {
+                return Some(comment["allow(".len()..comment.len()-1]
+                    .split(',').map(|x| String::from(x.trim())).collect());
+            }
+        }
+    }
+    None
+}
+
+fn find_allowed(text: &str) -> Allowed {
+    let mut allowed = Allowed {
+        file: HashSet::new(),
+        blocks: vec![],
+        lines: vec![None],  // lines are numbered from one
+    };
+    let mut block = None;
+    for line in text.lines() {
+        if line.starts_with("html ") || line.starts_with("html\t") {
+            block = Some(allowed.blocks.len());
+            allowed.blocks.push(HashSet::new());
+        } else if line.len() > 0 && !line.starts_with(" ")
+            && !line.starts_with("\t") && !line.starts_with("#")
+        {
+            block = None;
+        }
+        allowed.lines.push(block);
+        if let Some(rules) = allow_comment(line) {
+            match block {
+                Some(idx) if !line.starts_with("#") => {
+                    allowed.blocks[idx].extend(rules.into_iter());
+                }
+                _ => allowed.file.extend(rules.into_iter()),
+            }
+        }
+    }
+    allowed
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.chars().all(|c| match c {
+        'a'...'z' | '0'...'9' | '_' => true,
+        _ => false,
+    })
+}
+
+fn is_upper_case(name: &str) -> bool {
+    name.chars().all(|c| match c {
+        'A'...'Z' | '0'...'9' | '_' => true,
+        _ => false,
+    })
+}
+
+impl Allowed {
+    fn is_allowed(&self, block: Option<usize>, rule: &str) -> bool {
+        self.file.contains(rule) ||
+            block.map(|b| self.blocks[b].contains(rule)).unwrap_or(false)
+    }
+}
+
+impl<'a> Linter<'a> {
+    fn enabled(&self, rule: &str) -> bool {
+        !self.config.disabled.contains(rule) &&
+            !self.allowed.is_allowed(Some(self.block), rule)
+    }
+    fn name(&mut self, kind: &str, name: &String) {
+        if self.enabled(NAMING_STYLE) && !is_snake_case(name) {
+            self.messages.push(format!(
+                "[{}] {} {:?} in {:?} should be snake_case",
+                NAMING_STYLE, kind, name, self.block_name));
+        }
+    }
+    fn target(&mut self, target: &Target) {
+        match target {
+            &Target::Name(ref name) => self.name("Variable", name),
+            &Target::Destructure(ref names) => {
+                for name in names.iter() {
+                    self.name("Variable", name);
+                }
+            }
+        }
+    }
+    fn statements(&mut self, statements: &Vec<Statement>, depth: usize) {
+        // Every template is reported only once
+        if depth > self.config.max_depth && !self.depth_reported &&
+            self.enabled(MAX_NESTING_DEPTH)
+        {
+            self.messages.push(format!(
+                "[{}] {:?} is nested deeper than {} levels",
+                MAX_NESTING_DEPTH, self.block_name, self.config.max_depth));
+            self.depth_reported = true;
+        }
+        for st in statements.iter() {
+            match st {
+                &Statement::Let(ref name, _) | &Statement::Store(ref name, _)
+                => self.name("Variable", name),
+                &Statement::Element { ref body, .. } => {
+                    self.statements(body, depth+1);
+                }
+                &Statement::Condition(ref branches, ref fallback) => {
+                    for &(_, ref body) in branches.iter() {
+                        self.statements(body, depth+1);
+                    }
+                    if let &Some(ref body) = fallback {
+                        self.statements(body, depth+1);
+                    }
+                }
+                &Statement::Match(_, ref cases, ref fallback) => {
+                    for &(_, ref body) in cases.iter() {
+                        self.statements(body, depth+1);
+                    }
+                    if let &Some(ref body) = fallback {
+                        self.statements(body, depth+1);
+                    }
+                }
+                &Statement::Try(ref body, ref name, ref fallback) => {
+                    if let &Some(ref name) = name {
+                        self.name("Variable", name);
+                    }
+                    self.statements(body, depth+1);
+                    self.statements(fallback, depth+1);
+                }
+                &Statement::ForOf { ref index, ref target, ref body,
+                                    ref fallback, .. } => {
+                    if let &Some(ref index) = index {
+                        self.name("Variable", index);
+                    }
+                    self.target(target);
+                    self.statements(body, depth+1);
+                    if let &Some(ref body) = fallback {
+                        self.statements(body, depth+1);
+                    }
+                }
+                &Statement::ForIn { ref name, ref value, ref body,
+                                    ref fallback, .. } => {
+                    self.name("Variable", name);
+                    if let &Some(ref value) = value {
+                        self.target(value);
+                    }
+                    self.statements(body, depth+1);
+                    if let &Some(ref body) = fallback {
+                        self.statements(body, depth+1);
+                    }
+                }
+                &Statement::Format(_) | &Statement::Output(_)
+                | &Statement::Link(_) | &Statement::Call { .. } => {}
+            }
+        }
+    }
+}
+
+// Returns list of rule violations
+pub fn lint(ast: &Ast, text: &str, config: &Config) -> Vec<String> {
+    let allowed = find_allowed(text);
+    let mut messages = vec![];
+    for &(line, msg) in ::parser::deprecated_syntax(text).iter() {
+        let block = allowed.lines.get(line as usize).and_then(|x| *x);
+        if !config.disabled.contains(DEPRECATED_SYNTAX) &&
+            !allowed.is_allowed(block, DEPRECATED_SYNTAX)
+        {
+            messages.push(format!("[{}] line {}: {}",
+                                  DEPRECATED_SYNTAX, line, msg));
+        }
+    }
+    let mut block_num = 0;
+    for block in ast.blocks.iter() {
+        match block {
+            &Block::Html { ref name, ref params, ref events, ref statements }
+            => {
+                let mut linter = Linter {
+                    config: config,
+                    allowed: &allowed,
+                    block: block_num,
+                    block_name: name,
+                    depth_reported: false,
+                    messages: vec![],
+                };
+                if linter.enabled(NAMING_STYLE) && !is_snake_case(name) {
+                    linter.messages.push(format!(
+                        "[{}] Template {:?} should be snake_case",
+                        NAMING_STYLE, name));
+                }
+                for param in params.iter() {
+                    linter.name("Parameter", &param.name);
+                }
+                for event in events.iter() {
+                    linter.name("Event", event);
+                }
+                linter.statements(statements, 0);
+                messages.extend(linter.messages.into_iter());
+                block_num += 1;
+            }
+            &Block::Const(ref name, _) => {
+                if !config.disabled.contains(NAMING_STYLE) &&
+                    !allowed.is_allowed(None, NAMING_STYLE) &&
+                    !is_snake_case(name) && !is_upper_case(name)
+                {
+                    messages.push(format!(
+                        "[{}] Constant {:?} should be snake_case or \
+                         UPPER_CASE", NAMING_STYLE, name));
+                }
+            }
+            _ => {}
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod test {
+    use parser::parse_string;
+    use super::{Config, lint};
+
+    fn check(text: &str, config: &Config) -> Vec<String> {
+        lint(&parse_string(text).unwrap(), text, config)
+    }
+
+    #[test]
+    fn naming_style() {
+        let text = "const PAGE_SIZE = 10\nconst badConst = 1\n\
+                    html myList(someItems) events onDelete:\n\
+                    \x20 let goodName = someItems\n\
+                    \x20 for oneItem of goodName:\n    \"{oneItem}\"\n";
+        assert_eq!(check(text, &Config::new()), vec![
+            "[naming-style] Constant \"badConst\" should be snake_case \
+             or UPPER_CASE",
+            "[naming-style] Template \"myList\" should be snake_case",
+            "[naming-style] Parameter \"someItems\" in \"myList\" \
+             should be snake_case",
+            "[naming-style] Event \"onDelete\" in \"myList\" \
+             should be snake_case",
+            "[naming-style] Variable \"goodName\" in \"myList\" \
+             should be snake_case",
+            "[naming-style] Variable \"oneItem\" in \"myList\" \
+             should be snake_case",
+        ]);
+    }
+
+    #[test]
+    fn max_nesting_depth() {
+        let text = "html deep():\n  div\n    div\n      div\n        \"x\"\n";
+        let mut config = Config::new();
+        assert_eq!(check(text, &config), Vec::<String>::new());
+        config.parse("max-nesting-depth = 2").unwrap();
+        assert_eq!(check(text, &config), vec![
+            "[max-nesting-depth] \"deep\" is nested deeper than 2 levels"]);
+    }
+
+    #[test]
+    fn deprecated_syntax() {
+        let text = "html foo(x):\n  if !x:\n    \"x\"\n";
+        assert_eq!(check(text, &Config::new()), vec![
+            "[deprecated-syntax] line 2: `!` is deprecated, \
+             use `not` instead"]);
+    }
+
+    #[test]
+    fn config() {
+        let mut config = Config::new();
+        config.parse("# comment\n\nnaming-style = off\n\
+                      max-nesting-depth = 3\n").unwrap();
+        assert_eq!(check("html fooBar():\n  \"x\"\n", &config),
+            Vec::<String>::new());
+        assert_eq!(config.max_depth, 3);
+        config.parse("naming-style = on").unwrap();
+        assert_eq!(check("html fooBar():\n  \"x\"\n", &config).len(), 1);
+
+        assert_eq!(Config::new().parse("no-such-rule = on"),
+            Err(String::from("Line 1: unknown rule \"no-such-rule\"")));
+        assert_eq!(Config::new().parse("naming-style"),
+            Err(String::from("Line 1: expected `rule = value`")));
+        assert_eq!(Config::new().parse("\nnaming-style = 1"),
+            Err(String::from("Line 2: naming-style must be `on` or `off`")));
+        assert_eq!(Config::new().parse("max-nesting-depth = x"),
+            Err(String::from("Line 1: max-nesting-depth must be a number")));
+    }
+
+    #[test]
+    fn allow_comments() {
+        let text = "html fooBar(someArg):  # marafet: allow(naming-style)\n\
+                    \x20 \"{someArg}\"\n\
+                    html barBaz():\n  \"x\"\n";
+        assert_eq!(check(text, &Config::new()), vec![
+            "[naming-style] Template \"barBaz\" should be snake_case"]);
+
+        let text = "# marafet: allow(naming-style, deprecated-syntax)\n\
+                    html fooBar(x):\n  if !x:\n    \"x\"\n\
+                    html barBaz():\n  \"x\"\n";
+        assert_eq!(check(text, &Config::new()), Vec::<String>::new());
+    }
+}
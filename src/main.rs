@@ -1,17 +1,23 @@
 extern crate argparse;
 
+extern crate marafet_util as util;
 extern crate marafet_parser as parser;
 extern crate marafet_css as css;
 extern crate marafet_es5citojs as es5citojs;
 
+use std::env;
 use std::fs::File;
+use std::collections::HashMap;
 use std::io::{Read, Write, BufWriter};
 use std::io::{stdin, stdout, stderr};
 use std::io::Error as IoError;
-use std::path::{PathBuf, Path};
+use std::path::{PathBuf, Path, Component};
 use std::process::exit;
 
 use argparse::{ArgumentParser, Parse, ParseOption, Collect, StoreTrue};
+use util::Scoping;
+
+mod lint;
 
 
 fn read_file<R: Read>(f: Result<R, IoError>) -> Result<String, IoError> {
@@ -22,17 +28,295 @@ fn read_file<R: Read>(f: Result<R, IoError>) -> Result<String, IoError> {
     }
 }
 
+// Absolute path with `.` and `..` resolved, without following symlinks
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut res = env::current_dir().unwrap();
+    for item in path.components() {
+        match item {
+            Component::CurDir => {}
+            Component::ParentDir => { res.pop(); }
+            _ => res.push(item.as_os_str()),
+        }
+    }
+    res
+}
+
+// Block name unique for every file in the project, e.g. `components_button`
+// for `components/button.mft`, the path is relative to the `root`
+fn path_block_name(path: &Path, root: &Path) -> Result<String, String> {
+    let path = normalize_path(path);
+    let root = normalize_path(root);
+    let relative = match path.strip_prefix(&root) {
+        Ok(relative) => relative.with_extension(""),
+        Err(_) => {
+            return Err(format!("File {:?} is outside of the root {:?}, \
+                                use --root or --block-name", path, root));
+        }
+    };
+    let mut names = vec![];
+    for item in relative.components() {
+        if let Component::Normal(name) = item {
+            names.push(name.to_string_lossy().chars().map(|c| match c {
+                'a'...'z' | 'A'...'Z' | '0'...'9' | '-' => c,
+                _ => '_',
+            }).collect::<String>());
+        }
+    }
+    Ok(names.join("_"))
+}
+
+fn class_map_json(pairs: &Vec<(String, String)>) -> String {
+    let mut res = String::from("{");
+    for (idx, &(ref name, ref scoped)) in pairs.iter().enumerate() {
+        if idx > 0 {
+            res.push_str(",");
+        }
+        res.push_str(&format!("\n    {:?}: {:?}", name, scoped));
+    }
+    res.push_str("\n}\n");
+    res
+}
+
+// Writes file (or stdout for `-`), or only compares it in check mode
+fn write_output(filename: &Path, data: &[u8], check: bool) {
+    if filename == Path::new("-") {
+        stdout().write_all(data).unwrap();
+    } else if check {
+        check_file(filename, data);
+    } else {
+        let res = File::create(filename).map(BufWriter::new)
+            .and_then(|mut f| f.write_all(data));
+        if let Err(e) = res {
+            println!("Error writing file {:?}: {}", filename, e);
+            exit(2);
+        }
+    }
+}
+
+fn check_file(filename: &Path, data: &[u8]) {
+    let mut buf = Vec::new();
+    match File::open(filename).and_then(|mut f| f.read_to_end(&mut buf)) {
+        Ok(_) if &buf[..] == data => {}
+        Ok(_) => {
+            println!("File {:?} is out of date", filename);
+            exit(3);
+        }
+        Err(e) => {
+            println!("Error reading file {:?}: {}", filename, e);
+            exit(3);
+        }
+    }
+}
+
+// Parses `name=value` definition of a CSS variable
+fn parse_var(line: &str, vars: &mut HashMap<String, String>)
+    -> Result<(), String>
+{
+    let mut pair = line.splitn(2, '=');
+    let name = pair.next().unwrap().trim();
+    match pair.next() {
+        Some(value) if name.len() > 0 => {
+            vars.insert(String::from(name), String::from(value.trim()));
+            Ok(())
+        }
+        _ => Err(format!("expected `name=value`, got {:?}", line)),
+    }
+}
+
+// Reads variables from --css-vars-file, then applies --css-var on top
+fn load_vars(vars: &[String], vars_file: Option<&PathBuf>)
+    -> Result<HashMap<String, String>, String>
+{
+    let mut css_vars = HashMap::new();
+    if let Some(filename) = vars_file {
+        try!(read_file(File::open(filename))
+            .map_err(|e| format!("{}", e))
+            .and_then(|text| {
+                for line in text.lines() {
+                    let line = line.trim();
+                    if line.len() > 0 && !line.starts_with("#") {
+                        try!(parse_var(line, &mut css_vars));
+                    }
+                }
+                Ok(())
+            })
+            .map_err(|e| format!("Error reading file {:?}: {}",
+                                 filename, e)));
+    }
+    for var in vars.iter() {
+        try!(parse_var(var, &mut css_vars)
+            .map_err(|e| format!("Bad --css-var: {}", e)));
+    }
+    Ok(css_vars)
+}
+
+fn parse_file(source: &Path) -> (String, parser::Ast) {
+    let fileresult = if source == Path::new("-") {
+        read_file(Ok(stdin()))
+    } else {
+        read_file(File::open(source))
+    };
+    let body = match fileresult {
+        Ok(data) => data,
+        Err(e) => {
+            writeln!(&mut stderr(), "Error reading file {:?}: {}", source, e)
+                .unwrap();
+            exit(1);
+        }
+    };
+
+    let ast = match parser::parse_string(&body[..]) {
+        Ok(ast) => ast,
+        Err(e) => {
+            println!("Error parsing file {:?}: {}", source, e);
+            exit(1);
+        }
+    };
+    (body, ast)
+}
+
+// Warns about syntax which will be removed in future versions
+fn warn_deprecated(source: &Path, body: &str) {
+    for &(line, msg) in parser::deprecated_syntax(body).iter() {
+        writeln!(&mut stderr(), "Warning: {}: line {}: {}",
+                 source.display(), line, msg).unwrap();
+    }
+}
+
+// Implements `marafet check` and `marafet lint` subcommands
+fn check_command(mut args: Vec<String>, run_lint: bool) -> i32 {
+    let mut source = PathBuf::new();
+    let mut globals = Vec::<String>::new();
+    let mut config_file = None::<PathBuf>;
+    let mut vars = Vec::<String>::new();
+    let mut vars_file = None::<PathBuf>;
+    {
+        let mut ap = ArgumentParser::new();
+        if run_lint {
+            ap.set_description("Checks .mft file and reports style issues");
+            ap.refer(&mut config_file)
+                .add_option(&["-c", "--config"], ParseOption,
+                    "Lint configuration file, consisting of `rule = on|off` \
+                     and `max-nesting-depth = N` lines");
+        } else {
+            ap.set_description("Checks .mft file without writing output");
+        }
+        ap.refer(&mut source)
+            .required()
+            .add_option(&["-f", "--file"], Parse, "Input file name");
+        ap.refer(&mut globals)
+            .add_option(&["--global"], Collect,
+                "Allow templates to use javascript global variable, \
+                 if any are given, other undefined names are errors");
+        ap.refer(&mut vars)
+            .add_option(&["--css-var"], Collect,
+                "Set CSS variable (`name=value`)");
+        ap.refer(&mut vars_file)
+            .add_option(&["--css-vars-file"], ParseOption,
+                "Read CSS variables from file, one `name = value` per line");
+        let cmd = args.remove(1);
+        args[0] = format!("{} {}", args[0], cmd);
+        match ap.parse(args, &mut stdout(), &mut stderr()) {
+            Ok(()) => {}
+            Err(code) => return code,
+        }
+    }
+
+    let mut config = lint::Config::new();
+    if let Some(filename) = config_file {
+        let res = read_file(File::open(&filename))
+            .map_err(|e| format!("{}", e))
+            .and_then(|text| config.parse(&text));
+        if let Err(e) = res {
+            println!("Error reading config {:?}: {}", filename, e);
+            return 1;
+        }
+    }
+    let css_vars = match load_vars(&vars, vars_file.as_ref()) {
+        Ok(vars) => vars,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        }
+    };
+    let (body, ast) = parse_file(&source);
+    if !run_lint {
+        // lint reports it as `deprecated-syntax` unless it's turned off
+        warn_deprecated(&source, &body);
+    }
+    let block_name = source.file_stem().unwrap().to_string_lossy()
+        .into_owned();
+    let css_settings = css::Settings {
+        block_name: &block_name,
+        vars: &css_vars,
+        scoping: Scoping::BlockClass,
+    };
+    if let Err(err) = css::generate(&mut Vec::new(), &ast, &css_settings) {
+        println!("{}", err);
+        return 1;
+    }
+    let settings = es5citojs::Settings {
+        block_name: "",
+        scoping: Scoping::BlockClass,
+        css_text: None,
+        auto_load_css: false,
+        css_nonce: None,
+        export_css: false,
+        class_map: &[],
+        use_amd: false,
+        debug: false,
+        globals: &globals,
+        amd_name: "",
+    };
+    match es5citojs::check(&ast, &settings) {
+        Ok(warnings) => {
+            for warning in warnings.iter() {
+                writeln!(&mut stderr(), "Warning: {}", warning).unwrap();
+            }
+        }
+        Err(err) => {
+            println!("{}", err);
+            return 1;
+        }
+    }
+    if run_lint {
+        let messages = lint::lint(&ast, &body, &config);
+        for msg in messages.iter() {
+            println!("{}: {}", source.display(), msg);
+        }
+        if messages.len() > 0 {
+            return 1;
+        }
+    }
+    return 0;
+}
 
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(|x| &x[..]) {
+        Some("check") => exit(check_command(args, false)),
+        Some("lint") => exit(check_command(args, true)),
+        _ => {}
+    }
+
     let mut source = PathBuf::new();
     let mut use_amd = false;
     let mut amd_name = None::<String>;
     let mut output_js = None::<PathBuf>;
     let mut output_css = None::<PathBuf>;
     let mut block_name = None::<String>;
+    let mut root = PathBuf::from(".");
+    let mut scoping_name = String::from("block");
+    let mut output_class_map = None::<PathBuf>;
     let mut vars = Vec::<String>::new();
+    let mut vars_file = None::<PathBuf>;
     let mut print_ast = false;
     let mut css_load = false;
+    let mut css_nonce = None::<String>;
+    let mut export_css = false;
+    let mut debug = false;
+    let mut check = false;
+    let mut globals = Vec::<String>::new();
     {
         let mut ap = ArgumentParser::new();
         ap.set_description("Compiles .mft file to a CSS and/or JS file");
@@ -54,58 +338,121 @@ fn main() {
                  prepended to every classname in CSS and HTML to limit scope \
                  of style for this block only). By default derived from file \
                  name");
+        ap.refer(&mut root)
+            .add_option(&["--root"], Parse,
+                "Project directory, block names derived from the path of \
+                 the file are relative to it (default is current directory)");
+        ap.refer(&mut scoping_name)
+            .add_option(&["--scoping"], Parse,
+                "How class names are made unique: `block` (add block name \
+                 as a class, the default), `prefix` (also prefix every class \
+                 with the block name, which by default is derived from the \
+                 whole path) or `hash` (replace classes by short hashed \
+                 names, use --class-map to get the mapping)");
+        ap.refer(&mut output_class_map)
+            .add_option(&["--class-map"], ParseOption,
+                "Output JSON file mapping class names to the names in the \
+                 generated CSS");
         ap.refer(&mut vars)
-            .add_option(&["--css-var"], Collect, "Set CSS variable");
+            .add_option(&["--css-var"], Collect,
+                "Set CSS variable (`name=value`), overrides default value \
+                 of the parameter of the css block");
+        ap.refer(&mut vars_file)
+            .add_option(&["--css-vars-file"], ParseOption,
+                "Read CSS variables from file, one `name = value` per line. \
+                 Variables set by --css-var take precedence");
         ap.refer(&mut print_ast)
             .add_option(&["--print-ast"], StoreTrue, "Print AST to stdout");
         ap.refer(&mut css_load)
             .add_option(&["--auto-load-css"], StoreTrue,
                 "Insert css load code to the Javascript code");
+        ap.refer(&mut css_nonce)
+            .add_option(&["--css-nonce"], ParseOption,
+                "CSP nonce of the style element inserted by \
+                 --auto-load-css");
+        ap.refer(&mut export_css)
+            .add_option(&["--export-css"], StoreTrue,
+                "Export stylesheet as `css`, mapping of class names as \
+                 `classes` (unless --scoping=block) and \
+                 `load_css({root, nonce, adopt})` function from AMD module. \
+                 Function inserts stylesheet into the document or a shadow \
+                 root, only once per root");
+        ap.refer(&mut debug)
+            .add_option(&["--debug"], StoreTrue,
+                "Insert runtime checks of template parameter types to the \
+                 Javascript code");
+        ap.refer(&mut globals)
+            .add_option(&["--global"], Collect,
+                "Allow templates to use javascript global variable (common \
+                 ones like window, document, String or Math are always \
+                 allowed). If any are given, other undefined names are \
+                 errors rather than warnings");
+        ap.refer(&mut check)
+            .add_option(&["--check"], StoreTrue,
+                "Don't write output files, but fail if they differ from \
+                 the compiled code");
         ap.parse_args_or_exit();
     }
 
-    let block_name = block_name.unwrap_or(
-            String::from(source.file_stem().unwrap().to_str().unwrap()));
-
-    let fileresult = if Path::new(&source) == Path::new("-") {
-        read_file(Ok(stdin()))
-    } else {
-        read_file(File::open(&source))
-    };
-    let body = match fileresult {
-        Ok(data) => data,
-        Err(e) => {
-            writeln!(&mut stderr(), "Error reading file {:?}: {}", source, e)
-                .unwrap();
+    if export_css && !use_amd {
+        println!("--export-css requires --amd");
+        exit(1);
+    }
+    if css_nonce.is_some() && !css_load {
+        println!("--css-nonce requires --auto-load-css");
+        exit(1);
+    }
+    let scoping = match Scoping::from_name(&scoping_name) {
+        Some(scoping) => scoping,
+        None => {
+            println!("Bad --scoping {:?}, expected block, prefix or hash",
+                     scoping_name);
             exit(1);
         }
     };
+    let block_name = block_name.unwrap_or_else(|| match scoping {
+        Scoping::BlockClass => {
+            String::from(source.file_stem().unwrap().to_str().unwrap())
+        }
+        Scoping::Prefix | Scoping::Hash => {
+            match path_block_name(&source, &root) {
+                Ok(name) => name,
+                Err(e) => {
+                    println!("{}", e);
+                    exit(1);
+                }
+            }
+        }
+    });
 
-    let ast = match parser::parse_string(&body[..]) {
-        Ok(ast) => ast,
+    let css_vars = match load_vars(&vars, vars_file.as_ref()) {
+        Ok(vars) => vars,
         Err(e) => {
-            println!("Error parsing file {:?}: {}", source, e);
+            println!("{}", e);
             exit(1);
         }
     };
 
+    let (body, ast) = parse_file(&source);
+    warn_deprecated(&source, &body);
+
     if print_ast {
         println!("--- Ast ---");
         println!("{:?}", ast);
     }
 
-    // println!("--- Css ---");
-    // css::generate(&mut stdout(), &ast, &css::Settings {
-    //     block_name: &block_name,
-    //     vars: &Default::default(),
-    //     }).unwrap();
-
-    let css_text = if css_load {
+    let css_settings = css::Settings {
+        block_name: &block_name,
+        vars: &css_vars,
+        scoping: scoping,
+    };
+    let css_text = if css_load || export_css || output_css.is_some() {
         let mut buf = Vec::new();
-        css::generate(&mut buf, &ast, &css::Settings {
-            block_name: &block_name,
-            vars: &Default::default(),
-            }).unwrap();
+        let res = css::generate(&mut buf, &ast, &css_settings);
+        if let Err(err) = res {
+            println!("{}", err);
+            exit(1);
+        }
         let string = String::from_utf8(buf).unwrap();
         if print_ast {
             println!("--- CSS ---");
@@ -115,33 +462,43 @@ fn main() {
     } else {
         None
     };
+    if let (Some(filename), Some(text)) = (output_css, css_text.as_ref()) {
+        write_output(&filename, text.as_bytes(), check);
+    }
+    let class_map = css::class_map(&ast, &css_settings);
+    if let Some(filename) = output_class_map {
+        write_output(&filename, class_map_json(&class_map).as_bytes(),
+                     check);
+    }
     if let Some(filename) = output_js {
         let sourcepath = source.with_extension("");
         let settings = es5citojs::Settings {
             block_name: &block_name[..],
+            scoping: scoping,
             css_text: css_text.as_ref().map(|x| &x[..]),
+            auto_load_css: css_load,
+            css_nonce: css_nonce.as_ref().map(|x| &x[..]),
+            export_css: export_css,
+            class_map: &class_map,
             use_amd: use_amd,
+            debug: debug,
+            globals: &globals,
             amd_name: amd_name.as_ref().map(|x| &x[..]).unwrap_or(
                 sourcepath.to_str().unwrap()),
         };
-        let res;
-        if Path::new(&filename) == Path::new("-") {
-            res = es5citojs::generate(&mut BufWriter::new(stdout()),
-                                      &ast, &settings);
-        } else {
-            let mut file = match File::create(&filename).map(BufWriter::new) {
-                Ok(f) => f,
-                Err(e) => {
-                    println!("Error opening file {:?}: {}", filename, e);
-                    exit(2);
+        let mut buf = Vec::new();
+        match es5citojs::generate(&mut buf, &ast, &settings) {
+            Ok(warnings) => {
+                for warning in warnings.iter() {
+                    writeln!(&mut stderr(), "Warning: {}", warning).unwrap();
                 }
-            };
-            res = es5citojs::generate(&mut file, &ast, &settings);
-        }
-        if let Err(err) = res {
-            println!("{}", err);
-            exit(1);
+            }
+            Err(err) => {
+                println!("{}", err);
+                exit(1);
+            }
         }
+        write_output(&filename, &buf, check);
     }
 }
 
@@ -0,0 +1,219 @@
+// Runs the `marafet` binary on files in a temporary directory
+use std::env;
+use std::fs::{File, create_dir_all, remove_dir_all};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+
+fn tmpdir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("marafet-test-{}", name));
+    remove_dir_all(&dir).ok();
+    create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &PathBuf, data: &str) {
+    File::create(path).and_then(|mut f| f.write_all(data.as_bytes()))
+        .unwrap();
+}
+
+fn read(path: &PathBuf) -> String {
+    let mut buf = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut buf)).unwrap();
+    buf
+}
+
+fn marafet(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_marafet"))
+        .current_dir(dir).args(args).output().unwrap()
+}
+
+#[test]
+fn check_mode() {
+    let dir = tmpdir("check");
+    write(&dir.join("btn.mft"), "html btn(x):\n  button\n    \"{x}\"\n");
+    let out = marafet(&dir, &["-f", "btn.mft", "--js", "btn.js"]);
+    assert!(out.status.success());
+    let compiled = read(&dir.join("btn.js"));
+
+    let out = marafet(&dir, &["-f", "btn.mft", "--js", "btn.js", "--check"]);
+    assert!(out.status.success());
+
+    write(&dir.join("btn.js"), &compiled.replace("button", "span"));
+    let out = marafet(&dir, &["-f", "btn.mft", "--js", "btn.js", "--check"]);
+    assert_eq!(out.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("out of date"));
+    // File is not rewritten in check mode
+    assert!(read(&dir.join("btn.js")) != compiled);
+}
+
+#[test]
+fn check_command() {
+    let dir = tmpdir("check-command");
+    write(&dir.join("ok.mft"), "html ok(x):\n  \"{x}\"\n");
+    write(&dir.join("bad.mft"), "html bad(x):\n  \"{x} {y}\"\n");
+    let out = marafet(&dir, &["check", "-f", "ok.mft"]);
+    assert!(out.status.success());
+    let out = marafet(&dir, &["check", "-f", "bad.mft"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("Warning: Undefined name \"y\" in \"bad\""));
+    let out = marafet(&dir, &["check", "-f", "bad.mft", "--global", "y"]);
+    assert!(out.status.success());
+    let out = marafet(&dir, &["check", "-f", "bad.mft", "--global", "z"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&out.stdout),
+        "Undefined name \"y\" in \"bad\"\n");
+    // Nothing is written by check
+    assert!(!dir.join("ok.js").exists());
+
+    write(&dir.join("style.mft"), "css:\n  .a\n    color: $color\n");
+    write(&dir.join("vars.txt"), "color = red\n");
+    let out = marafet(&dir, &["check", "-f", "style.mft"]);
+    assert_eq!(out.status.code(), Some(1));
+    let out = marafet(&dir, &["check", "-f", "style.mft",
+                              "--css-var", "color=red"]);
+    assert!(out.status.success());
+    let out = marafet(&dir, &["lint", "-f", "style.mft",
+                              "--css-vars-file", "vars.txt"]);
+    assert!(out.status.success());
+}
+
+#[test]
+fn deprecated_syntax() {
+    let dir = tmpdir("deprecated-syntax");
+    write(&dir.join("old.mft"), "html old(x):\n  if !x:\n    \"no\"\n");
+    let out = marafet(&dir, &["check", "-f", "old.mft"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stderr),
+        "Warning: old.mft: line 2: `!` is deprecated, use `not` instead\n");
+    let out = marafet(&dir, &["-f", "old.mft", "--js", "old.js"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("line 2: `!` is deprecated"));
+}
+
+#[test]
+fn lint_command() {
+    let dir = tmpdir("lint-command");
+    write(&dir.join("lint.mft"), "html fooBar(x):\n  \"{x}\"\n");
+    write(&dir.join("lint.cfg"), "naming-style = off\n");
+    let out = marafet(&dir, &["lint", "-f", "lint.mft"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&out.stdout),
+        "lint.mft: [naming-style] Template \"fooBar\" should be snake_case\n");
+    let out = marafet(&dir, &["lint", "-f", "lint.mft", "-c", "lint.cfg"]);
+    assert!(out.status.success());
+}
+
+#[test]
+fn css_vars() {
+    let dir = tmpdir("css-vars");
+    write(&dir.join("vars.mft"), "css(color=\"red\", size=\"1px\"):\n\
+                                  \x20 .a\n    color: $color\n\
+                                  \x20   margin: $size\n\
+                                  html vars():\n  .a\n");
+    write(&dir.join("vars.txt"), "# comment\ncolor = blue\n\nsize=2px\n");
+    let out = marafet(&dir, &["-f", "vars.mft", "--js", "-",
+        "--auto-load-css", "--css-vars-file", "vars.txt",
+        "--css-var", "size=3px"]);
+    assert!(out.status.success());
+    let js = String::from_utf8_lossy(&out.stdout);
+    assert!(js.contains("color: blue;"), "{}", js);
+    assert!(js.contains("margin: 3px;"), "{}", js);
+
+    write(&dir.join("bad.txt"), "color\n");
+    let out = marafet(&dir, &["-f", "vars.mft", "--js", "-",
+        "--auto-load-css", "--css-vars-file", "bad.txt"]);
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn css_output() {
+    let dir = tmpdir("css-output");
+    write(&dir.join("box.mft"), "css:\n  .box\n    color: red\n\
+                                 html box():\n  .box\n");
+    let out = marafet(&dir, &["-f", "box.mft", "--css", "box.css",
+                              "--js", "box.js"]);
+    assert!(out.status.success());
+    assert_eq!(read(&dir.join("box.css")),
+        ".box.box {\n    color: red;\n}\n\n");
+    // Stylesheet isn't embedded without --auto-load-css
+    assert!(!read(&dir.join("box.js")).contains("color: red"));
+
+    let out = marafet(&dir, &["-f", "box.mft", "--css", "box.css", "--check"]);
+    assert!(out.status.success());
+    write(&dir.join("box.css"), "");
+    let out = marafet(&dir, &["-f", "box.mft", "--css", "box.css", "--check"]);
+    assert_eq!(out.status.code(), Some(3));
+
+    let out = marafet(&dir, &["-f", "box.mft", "--css", "-"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout),
+        ".box.box {\n    color: red;\n}\n\n");
+}
+
+#[test]
+fn path_block_name() {
+    let dir = tmpdir("path-block-name");
+    create_dir_all(dir.join("components")).unwrap();
+    write(&dir.join("components/my-button.mft"),
+        "css:\n  .x\n    color: red\n");
+    let out = marafet(&dir, &["-f", "components/my-button.mft",
+                              "--scoping", "prefix", "--css", "-"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout),
+        ".components_my-button.components_my-button-x {\n\
+         \x20   color: red;\n}\n\n");
+    let out = marafet(&dir, &["-f", "./components/../components/my-button.mft",
+        "--scoping", "hash", "--class-map", "-"]);
+    let hashed = String::from_utf8_lossy(&out.stdout).into_owned();
+    let out = marafet(&dir, &["-f", "components/my-button.mft",
+                              "--scoping", "hash", "--class-map", "-"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), hashed);
+    let out = marafet(&dir, &["-f", "components/my-button.mft",
+                              "--root", "components",
+                              "--scoping", "prefix", "--css", "-"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout),
+        ".my-button.my-button-x {\n\x20   color: red;\n}\n\n");
+    // No absolute path leaks into the block name
+    write(&dir.join("top.mft"), "css:\n  .x\n    color: red\n");
+    let out = marafet(&dir.join("components"), &["-f", "../top.mft",
+                              "--scoping", "prefix", "--css", "-"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stdout)
+        .contains("is outside of the root"));
+    let out = marafet(&dir, &["-f", "components/my-button.mft",
+                              "--scoping", "other", "--css", "-"]);
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn css_nonce() {
+    let dir = tmpdir("css-nonce");
+    write(&dir.join("box.mft"), "css:\n  .box\n    color: red\n\
+                                 html box():\n  .box\n");
+    let out = marafet(&dir, &["-f", "box.mft", "--js", "-",
+                              "--auto-load-css", "--css-nonce", "abc"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout)
+        .contains("_load_css({nonce: \"abc\"})"));
+    let out = marafet(&dir, &["-f", "box.mft", "--js", "-",
+                              "--css-nonce", "abc"]);
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn export_css() {
+    let dir = tmpdir("export-css");
+    write(&dir.join("box.mft"), "css:\n  .box\n    color: red\n\
+                                 html box():\n  .box\n");
+    let out = marafet(&dir, &["-f", "box.mft", "--js", "-", "--export-css"]);
+    assert_eq!(out.status.code(), Some(1));
+    let out = marafet(&dir, &["-f", "box.mft", "--js", "-", "--amd",
+                              "--export-css", "--scoping", "prefix"]);
+    assert!(out.status.success());
+    let js = String::from_utf8_lossy(&out.stdout);
+    assert!(js.contains("exports.css = "), "{}", js);
+    assert!(js.contains("exports.load_css = "), "{}", js);
+    assert!(js.contains("box: \"box-box\""), "{}", js);
+}